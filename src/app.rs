@@ -1,49 +1,91 @@
 use glam::{vec2, Vec2};
 use loki_draw::drawer::Drawer;
 use loki_draw::OpenglDrawer;
+use winit::dpi::PhysicalSize;
 use winit::event::WindowEvent;
 use winit::event_loop::EventLoopWindowTarget;
 use winit::keyboard::ModifiersState;
 
 use crate::app_frame::App;
+use crate::texture::TextureDrawer;
 use crate::Scene;
 
 pub struct OwOverlayApp<S: Scene> {
 	pub drawer: Option<OpenglDrawer>,
+	pub image_drawer: Option<TextureDrawer>,
 	pub modifiers_state: ModifiersState,
+	/// Size of the window in physical pixels
+	pub size: Vec2,
+	/// Scale factor reported by the window
+	pub scale_factor: f32,
+	/// Global scale multiplier from the config
+	pub config_scale: f32,
+	/// Whether the window's scale factor is applied at all
+	pub dpi_aware: bool,
+	/// Size of the window in config units
 	pub viewport: Vec2,
 	pub scene: S,
 }
 
 impl<S: Scene> OwOverlayApp<S> {
-	pub fn new(width: u32, height: u32, scene: S) -> Self {
+	pub fn new(config_scale: f32, dpi_aware: bool, scene: S) -> Self {
 		Self {
 			drawer: None,
+			image_drawer: None,
 			modifiers_state: ModifiersState::empty(),
-			viewport: vec2(width as f32, height as f32),
+			size: Vec2::ZERO,
+			scale_factor: 1.,
+			config_scale,
+			dpi_aware,
+			viewport: Vec2::ZERO,
 			scene,
 		}
 	}
-}
 
-impl<S: Scene> App for OwOverlayApp<S> {
-	fn resume_window(&mut self) {
-		self.drawer = Some(OpenglDrawer::new(self.viewport.x as u32, self.viewport.y as u32, 1.));
+	/// Scale from config units to physical pixels.
+	pub fn scale(&self) -> f32 {
+		match self.dpi_aware {
+			true => self.scale_factor * self.config_scale,
+			false => self.config_scale,
+		}
 	}
 
-	fn resize(&mut self, width: i32, height: i32) {
-		self.viewport = vec2(width as f32, height as f32);
+	fn update_viewport(&mut self) {
+		let scale = self.scale();
+		self.viewport = self.size / scale;
+		self.scene.resize(self.viewport);
 
 		if let Some(drawer) = &mut self.drawer {
-			drawer.resize(self.viewport, 1.);
+			drawer.resize(self.size, scale);
+		}
+
+		if let Some(image_drawer) = &mut self.image_drawer {
+			image_drawer.resize(self.size, scale);
 		}
 	}
+}
+
+impl<S: Scene> App for OwOverlayApp<S> {
+	fn resume_window(&mut self, size: PhysicalSize<u32>, scale_factor: f64) {
+		self.size = vec2(size.width as f32, size.height as f32);
+		self.scale_factor = scale_factor as f32;
+
+		let scale = self.scale();
+		self.drawer = Some(OpenglDrawer::new(size.width, size.height, scale));
+		self.image_drawer = Some(TextureDrawer::new(size.width, size.height, scale));
+		self.update_viewport();
+	}
+
+	fn resize(&mut self, width: i32, height: i32) {
+		self.size = vec2(width as f32, height as f32);
+		self.update_viewport();
+	}
 
 	fn draw(&mut self) {
 		self.scene.update();
 
-		if let Some(drawer) = &mut self.drawer {
-			self.scene.draw(self.viewport, drawer);
+		if let (Some(drawer), Some(image_drawer)) = (&mut self.drawer, &mut self.image_drawer) {
+			self.scene.draw(self.viewport, drawer, image_drawer);
 		}
 	}
 
@@ -55,7 +97,16 @@ impl<S: Scene> App for OwOverlayApp<S> {
 		match event {
 			WindowEvent::KeyboardInput { event, .. } => self.scene.inapp_key_event(event, self.modifiers_state),
 			WindowEvent::ModifiersChanged(modifiers) => self.modifiers_state = modifiers.state(),
+			// winit follows up with a resize to the new physical size
+			WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+				self.scale_factor = scale_factor as f32;
+				self.update_viewport();
+			}
 			_ => (),
 		}
 	}
+
+	fn exit(&mut self) {
+		self.scene.exit();
+	}
 }
@@ -5,6 +5,7 @@ use std::ffi::CString;
 use std::num::NonZeroU32;
 
 use raw_window_handle::HasRawWindowHandle;
+use winit::dpi::PhysicalSize;
 use winit::event::{Event, WindowEvent};
 use winit::event_loop::{EventLoop, EventLoopWindowTarget};
 use winit::window::{Window, WindowBuilder};
@@ -18,10 +19,11 @@ use glutin::surface::SwapInterval;
 use glutin_winit::{self, DisplayBuilder, GlWindow};
 
 pub trait App {
-	fn resume_window(&mut self);
+	fn resume_window(&mut self, size: PhysicalSize<u32>, scale_factor: f64);
 	fn resize(&mut self, width: i32, height: i32);
 	fn draw(&mut self);
 	fn handle_window_event(&mut self, event: WindowEvent, window_target: &EventLoopWindowTarget<()>);
+	fn exit(&mut self);
 }
 
 pub struct AppFrame {
@@ -174,7 +176,7 @@ impl AppFrame {
 						self.gl_display.get_proc_address(&CString::new(symbol).unwrap()) as *const _
 					});
 
-					app.resume_window();
+					app.resume_window(window.inner_size(), window.scale_factor());
 
 					// Try setting vsync.
 					if let Err(res) =
@@ -226,6 +228,7 @@ impl AppFrame {
 						gl_surface.swap_buffers(gl_context).unwrap();
 					}
 				}
+				Event::LoopExiting => app.exit(),
 				_ => (),
 			}
 		})?;
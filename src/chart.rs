@@ -0,0 +1,567 @@
+//! Timing practice on a chart of notes scrolling towards the keys, with hit judgements
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use serde::Deserialize;
+
+use crate::config::ChartProps;
+use crate::key::display_key;
+use crate::layout::DETAIL_COLOR;
+use crate::HudLine;
+
+/// osu!mania spreads its lanes over this many pixels.
+const OSU_PLAYFIELD_WIDTH: f32 = 512.;
+const CALIBRATION_NOTES: u32 = 16;
+const CALIBRATION_INTERVAL: Duration = Duration::from_millis(600);
+/// How long the last judgement stays in the HUD, in milliseconds
+const JUDGEMENT_DURATION: f32 = 500.;
+
+pub struct Note {
+	/// Time of the note from the start of the chart
+	pub time: Duration,
+	pub lane: usize,
+}
+
+/// Notes sorted by time.
+pub struct Chart {
+	notes: Vec<Note>,
+}
+
+#[derive(Deserialize)]
+struct ChartFile {
+	notes: Vec<NoteEntry>,
+}
+
+#[derive(Deserialize)]
+struct NoteEntry {
+	/// Time of the note in milliseconds
+	time: u64,
+	lane: usize,
+}
+
+impl Chart {
+	/// Loads a chart from an osu!mania `.osu` file, a `.csv` file of `time,lane` lines,
+	/// or a `.toml` file with a `notes` array, times being in milliseconds.
+	pub fn load(path: &Path, n_lanes: usize) -> Result<Self, Box<dyn Error>> {
+		let format = match path.extension().and_then(|ext| ext.to_str()) {
+			Some(format @ ("osu" | "csv" | "toml")) => format,
+			_ => return Err(format!("unknown chart format {}, expected .osu, .csv or .toml", path.display()).into()),
+		};
+
+		Self::parse(&fs::read_to_string(path)?, format, n_lanes)
+	}
+
+	/// Parses a chart in one of the formats of `load`, given by its extension.
+	fn parse(text: &str, format: &str, n_lanes: usize) -> Result<Self, Box<dyn Error>> {
+		let mut notes = match format {
+			"osu" => parse_osu(text, n_lanes)?,
+			"csv" => parse_csv(text)?,
+			_ => (toml::from_str::<ChartFile>(text)?.notes.into_iter())
+				.map(|note| Note {
+					time: Duration::from_millis(note.time),
+					lane: note.lane,
+				})
+				.collect(),
+		};
+
+		// lanes of .osu charts are already capped, but not the ones of the other formats
+		if let Some(note) = notes.iter().find(|note| note.lane >= n_lanes) {
+			return Err(format!(
+				"chart has a note in lane {} but there are only {} columns",
+				note.lane, n_lanes
+			)
+			.into());
+		}
+
+		notes.sort_by_key(|note| note.time);
+		Ok(Self { notes })
+	}
+
+	/// Evenly spaced notes going through every lane.
+	fn calibration(n_lanes: usize) -> Self {
+		let notes = (0..CALIBRATION_NOTES)
+			.map(|i| Note {
+				time: CALIBRATION_INTERVAL * i,
+				lane: i as usize % n_lanes.max(1),
+			})
+			.collect();
+
+		Self { notes }
+	}
+}
+
+fn parse_osu(text: &str, n_lanes: usize) -> Result<Vec<Note>, Box<dyn Error>> {
+	let mut section = "";
+	let mut keys = n_lanes;
+	let mut notes = Vec::new();
+
+	for line in text.lines().map(str::trim) {
+		if line.starts_with('[') && line.ends_with(']') {
+			section = line;
+			continue;
+		}
+
+		match section {
+			"[General]" if line.strip_prefix("Mode:").is_some_and(|mode| mode.trim() != "3") => {
+				return Err("only osu!mania charts are supported".into());
+			}
+			"[Difficulty]" if line.starts_with("CircleSize:") => {
+				keys = line["CircleSize:".len()..].trim().parse::<f32>()? as usize;
+			}
+			// x,y,time,type,... with the lane given by x, hold notes only counting for their head
+			"[HitObjects]" if !line.is_empty() => {
+				let fields = line.split(',').collect::<Vec<_>>();
+				if fields.len() < 3 {
+					return Err(format!("invalid hit object: {}", line).into());
+				}
+
+				let x = fields[0].parse::<f32>()?;
+				let time = fields[2].parse::<i64>()?;
+				let lane = (x * keys as f32 / OSU_PLAYFIELD_WIDTH).floor() as usize;
+
+				notes.push(Note {
+					time: Duration::from_millis(time.max(0) as u64),
+					lane: lane.min(keys.saturating_sub(1)),
+				});
+			}
+			_ => (),
+		}
+	}
+
+	Ok(notes)
+}
+
+fn parse_csv(text: &str) -> Result<Vec<Note>, Box<dyn Error>> {
+	let mut notes = Vec::new();
+
+	for (i, line) in text.lines().map(str::trim).enumerate() {
+		let Some((time, lane)) = line.split_once(',') else {
+			continue;
+		};
+
+		let (Ok(time), Ok(lane)) = (time.trim().parse::<u64>(), lane.trim().parse::<usize>()) else {
+			// there may be a header
+			if i == 0 {
+				continue;
+			}
+
+			return Err(format!("invalid note on line {}: {}", i + 1, line).into());
+		};
+
+		notes.push(Note {
+			time: Duration::from_millis(time),
+			lane,
+		});
+	}
+
+	Ok(notes)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Judgement {
+	Perfect,
+	Great,
+	Miss,
+}
+
+impl Judgement {
+	fn label(self) -> &'static str {
+		match self {
+			Judgement::Perfect => "Perfect",
+			Judgement::Great => "Great",
+			Judgement::Miss => "Miss",
+		}
+	}
+
+	fn color(self, props: &ChartProps) -> u32 {
+		match self {
+			Judgement::Perfect => props.perfect_color,
+			Judgement::Great => props.great_color,
+			Judgement::Miss => props.miss_color,
+		}
+	}
+}
+
+/// Milliseconds from `b` to `a`, negative if `a` is earlier.
+fn signed_ms(a: SystemTime, b: SystemTime) -> f32 {
+	match a.duration_since(b) {
+		Ok(d) => d.as_secs_f32() * 1000.,
+		Err(e) => -e.duration().as_secs_f32() * 1000.,
+	}
+}
+
+#[derive(Default)]
+struct Stats {
+	perfect: u32,
+	great: u32,
+	miss: u32,
+	/// Sum of the timing errors of the hits, in milliseconds
+	error_sum: f32,
+	/// Sum of the timing errors of the hits without the offset, for calibration
+	raw_error_sum: f32,
+}
+
+impl Stats {
+	fn judged(&self) -> u32 {
+		self.perfect + self.great + self.miss
+	}
+
+	fn hits(&self) -> u32 {
+		self.perfect + self.great
+	}
+
+	/// Accuracy from 0 to 100, a great being worth a third of a perfect.
+	fn accuracy(&self) -> f32 {
+		match self.judged() {
+			0 => 100.,
+			judged => (3 * self.perfect + self.great) as f32 / (3 * judged) as f32 * 100.,
+		}
+	}
+
+	fn mean_error(&self) -> f32 {
+		self.error_sum / self.hits().max(1) as f32
+	}
+}
+
+/// A run through the chart or through the calibration notes.
+struct Run {
+	chart_start: SystemTime,
+	calibrating: bool,
+	judged: Vec<bool>,
+	/// Index of the first note that isn't judged yet
+	first_pending: usize,
+	stats: Stats,
+	last_judgement: Option<(Judgement, f32, SystemTime)>,
+}
+
+pub struct ChartPractice {
+	pub props: ChartProps,
+	chart: Chart,
+	calibration_chart: Chart,
+	/// Added to the times of the notes when judging presses, in milliseconds
+	offset: f32,
+	run: Option<Run>,
+	/// Offset found by the last calibration
+	calibrated: Option<f32>,
+}
+
+impl ChartPractice {
+	pub fn new(chart: Chart, props: ChartProps, n_lanes: usize) -> Self {
+		Self {
+			offset: props.offset as f32,
+			props,
+			chart,
+			calibration_chart: Chart::calibration(n_lanes),
+			run: None,
+			calibrated: None,
+		}
+	}
+
+	pub fn control_keys(&self) -> [rdev::Key; 2] {
+		[self.props.start_key, self.props.calibrate_key]
+	}
+
+	fn notes(&self, calibrating: bool) -> &[Note] {
+		match calibrating {
+			true => &self.calibration_chart.notes,
+			false => &self.chart.notes,
+		}
+	}
+
+	/// Starts the chart or the calibration when pressing their key.
+	pub fn control_key_pressed(&mut self, key: rdev::Key, time: SystemTime) {
+		let calibrating = match key {
+			key if key == self.props.start_key => false,
+			key if key == self.props.calibrate_key => true,
+			_ => return,
+		};
+
+		self.run = Some(Run {
+			chart_start: time + Duration::from_secs_f32(self.props.lead_in.max(0.)),
+			calibrating,
+			judged: vec![false; self.notes(calibrating).len()],
+			first_pending: 0,
+			stats: Stats::default(),
+			last_judgement: None,
+		});
+	}
+
+	/// Judges a press on `lane` against its next note.
+	pub fn press(&mut self, lane: usize, time: SystemTime) {
+		let Some(run) = &self.run else {
+			return;
+		};
+
+		let notes = self.notes(run.calibrating);
+		let great_window = self.props.great_window as f32;
+
+		// the earliest note of the lane that can still be hit
+		let note = (run.first_pending..notes.len())
+			.filter(|&i| !run.judged[i] && notes[i].lane == lane)
+			.map(|i| (i, signed_ms(time, run.chart_start + notes[i].time)))
+			.find(|&(_, raw_error)| raw_error - self.offset <= great_window);
+
+		let Some((i, raw_error)) = note else {
+			return;
+		};
+
+		let error = raw_error - self.offset;
+		let judgement = match error.abs() {
+			e if e <= self.props.perfect_window as f32 => Judgement::Perfect,
+			e if e <= great_window => Judgement::Great,
+			e if e <= self.props.miss_window as f32 => Judgement::Miss,
+			// too early to be about this note
+			_ => return,
+		};
+
+		let Some(run) = &mut self.run else {
+			return;
+		};
+
+		run.judged[i] = true;
+		run.last_judgement = Some((judgement, error, time));
+
+		if judgement != Judgement::Miss {
+			run.stats.error_sum += error;
+			run.stats.raw_error_sum += raw_error;
+		}
+
+		match judgement {
+			Judgement::Perfect => run.stats.perfect += 1,
+			Judgement::Great => run.stats.great += 1,
+			Judgement::Miss => run.stats.miss += 1,
+		}
+
+		self.finish_if_done();
+	}
+
+	/// Misses the notes that went past without being hit.
+	pub fn update(&mut self, now: SystemTime) {
+		let great_window = self.props.great_window as f32;
+		let offset = self.offset;
+
+		let Some(run) = &mut self.run else {
+			return;
+		};
+
+		let notes = match run.calibrating {
+			true => &self.calibration_chart.notes,
+			false => &self.chart.notes,
+		};
+
+		while run.first_pending < notes.len() {
+			let i = run.first_pending;
+
+			if !run.judged[i] {
+				let note_time = run.chart_start + notes[i].time;
+				if signed_ms(now, note_time) - offset <= great_window {
+					break;
+				}
+
+				run.judged[i] = true;
+				run.stats.miss += 1;
+				run.last_judgement = Some((Judgement::Miss, great_window, now));
+			}
+
+			run.first_pending += 1;
+		}
+
+		self.finish_if_done();
+	}
+
+	fn finish_if_done(&mut self) {
+		let Some(run) = &self.run else {
+			return;
+		};
+
+		if !run.calibrating || !self.is_done(run) || run.stats.hits() == 0 {
+			return;
+		}
+
+		// the presses' average lateness becomes the offset
+		let offset = (run.stats.raw_error_sum / run.stats.hits() as f32).round();
+		self.offset = offset;
+		self.calibrated = Some(offset);
+	}
+
+	fn is_done(&self, run: &Run) -> bool {
+		run.stats.judged() as usize >= self.notes(run.calibrating).len()
+	}
+
+	/// Lanes of the notes left to hit, with the seconds left until they reach the keys.
+	pub fn visible_notes(&self, now: SystemTime) -> Vec<(usize, f32)> {
+		let Some(run) = &self.run else {
+			return Vec::new();
+		};
+
+		let notes = self.notes(run.calibrating);
+		(run.first_pending..notes.len())
+			.filter(|&i| !run.judged[i])
+			.map(|i| (notes[i].lane, signed_ms(run.chart_start + notes[i].time, now) / 1000.))
+			.collect()
+	}
+
+	pub fn hud_lines(&self, now: SystemTime) -> Vec<HudLine> {
+		let line = |text: String, color: u32, alpha: f32| HudLine {
+			text,
+			size: self.props.font_size,
+			color,
+			alpha,
+		};
+
+		let start_key = display_key(self.props.start_key);
+		let calibrate_key = display_key(self.props.calibrate_key);
+
+		let Some(run) = &self.run else {
+			return vec![
+				line(format!("Press {} to start the chart", start_key), self.props.color, 1.),
+				line(format!("Press {} to calibrate", calibrate_key), DETAIL_COLOR, 1.),
+			];
+		};
+
+		let stats = &run.stats;
+		let mut lines = vec![line(
+			format!(
+				"{:.2}% · {} Perfect · {} Great · {} Miss",
+				stats.accuracy(),
+				stats.perfect,
+				stats.great,
+				stats.miss
+			),
+			self.props.color,
+			1.,
+		)];
+
+		if let Some((judgement, error, time)) = run.last_judgement {
+			let age = now.duration_since(time).unwrap_or_default().as_millis() as f32;
+			let alpha = 1. - age / JUDGEMENT_DURATION;
+
+			if alpha > 0. {
+				let text = match judgement {
+					Judgement::Miss => judgement.label().to_string(),
+					_ => format!("{} {:+.0} ms", judgement.label(), error),
+				};
+
+				lines.push(line(text, judgement.color(&self.props), alpha));
+			}
+		}
+
+		if self.is_done(run) {
+			let summary = match (run.calibrating, self.calibrated) {
+				(true, Some(offset)) => format!("Offset calibrated to {:+.0} ms", offset),
+				_ => format!("Done! Mean timing {:+.0} ms", stats.mean_error()),
+			};
+
+			lines.push(line(summary, self.props.color, 1.));
+			lines.push(line(
+				format!("Press {} to retry, {} to calibrate", start_key, calibrate_key),
+				DETAIL_COLOR,
+				1.,
+			));
+		}
+
+		lines
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn lanes(chart: &Chart) -> Vec<usize> {
+		chart.notes.iter().map(|note| note.lane).collect()
+	}
+
+	#[test]
+	fn osu_lanes_follow_circle_size() {
+		let osu = "[General]
+			Mode: 3
+
+			[Difficulty]
+			CircleSize:4
+
+			[HitObjects]
+			448,192,300,1,0,0:0:0:0:
+			64,192,100,1,0,0:0:0:0:
+			192,192,200,128,0,400:0:0:0:0:
+			320,192,200,1,0,0:0:0:0:";
+
+		// the chart's 4 keys take precedence over the 7 columns
+		let chart = Chart::parse(osu, "osu", 7).unwrap();
+		assert_eq!(lanes(&chart), [0, 1, 2, 3]);
+		assert_eq!(chart.notes[1].time, Duration::from_millis(200));
+	}
+
+	#[test]
+	fn osu_rejects_other_modes() {
+		assert!(Chart::parse("[General]\nMode: 0\n", "osu", 4).is_err());
+	}
+
+	#[test]
+	fn csv_skips_its_header() {
+		let chart = Chart::parse("time,lane\n500,1\n250,0\n", "csv", 4).unwrap();
+		assert_eq!(lanes(&chart), [0, 1]);
+		assert_eq!(chart.notes[0].time, Duration::from_millis(250));
+
+		assert!(Chart::parse("250,0\ntime,lane\n", "csv", 4).is_err());
+	}
+
+	#[test]
+	fn csv_rejects_lanes_past_the_columns() {
+		assert!(Chart::parse("0,3\n", "csv", 4).is_ok());
+		assert!(Chart::parse("0,4\n", "csv", 4).is_err());
+	}
+
+	#[test]
+	fn judgements_follow_the_windows() {
+		let chart = Chart::parse("0,0\n1000,0\n2000,0\n3000,0\n", "csv", 4).unwrap();
+		let props = ChartProps::default();
+		let start_key = props.start_key;
+		let mut practice = ChartPractice::new(chart, props, 4);
+
+		let t0 = SystemTime::UNIX_EPOCH;
+		practice.control_key_pressed(start_key, t0);
+		let chart_start = t0 + Duration::from_secs_f32(practice.props.lead_in);
+		let at = |ms: i64| match ms {
+			0.. => chart_start + Duration::from_millis(ms as u64),
+			_ => chart_start - Duration::from_millis(-ms as u64),
+		};
+
+		// way too early to be about the first note
+		practice.press(0, at(-500));
+		assert_eq!(practice.run.as_ref().unwrap().stats.judged(), 0);
+
+		practice.press(0, at(-30));
+		practice.press(0, at(1080));
+		practice.press(0, at(2000 - 130));
+		// the last note is left to scroll past
+		practice.update(at(3200));
+
+		let stats = &practice.run.as_ref().unwrap().stats;
+		assert_eq!((stats.perfect, stats.great, stats.miss), (1, 1, 2));
+		assert_eq!(stats.mean_error().round(), 25.);
+		assert!(practice.is_done(practice.run.as_ref().unwrap()));
+	}
+
+	#[test]
+	fn calibration_sets_the_mean_lateness_as_offset() {
+		let props = ChartProps::default();
+		let calibrate_key = props.calibrate_key;
+		let mut practice = ChartPractice::new(Chart { notes: Vec::new() }, props, 4);
+
+		let t0 = SystemTime::UNIX_EPOCH;
+		practice.control_key_pressed(calibrate_key, t0);
+		let chart_start = t0 + Duration::from_secs_f32(practice.props.lead_in);
+
+		for (i, note) in Chart::calibration(4).notes.iter().enumerate() {
+			let lateness = if i % 2 == 0 { 10 } else { 30 };
+			practice.press(note.lane, chart_start + note.time + Duration::from_millis(lateness));
+		}
+
+		assert_eq!(practice.calibrated, Some(20.));
+		assert_eq!(practice.offset, 20.);
+	}
+}
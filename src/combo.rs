@@ -0,0 +1,169 @@
+//! Combo of presses on the lanes, broken by pausing for too long
+
+use std::time::{Duration, SystemTime};
+
+use crate::config::ComboProps;
+use crate::layout::DETAIL_COLOR;
+use crate::HudLine;
+
+pub struct ComboCounter {
+	props: ComboProps,
+	combo: u32,
+	max_combo: u32,
+	last_press: Option<SystemTime>,
+	/// Time the last milestone was reached, for the flash
+	milestone_at: Option<SystemTime>,
+	/// Time and length of the last broken combo, for the break effect
+	broken: Option<(SystemTime, u32)>,
+}
+
+impl ComboCounter {
+	pub fn new(props: ComboProps) -> Self {
+		Self {
+			props,
+			combo: 0,
+			max_combo: 0,
+			last_press: None,
+			milestone_at: None,
+			broken: None,
+		}
+	}
+
+	fn max_gap(&self) -> Duration {
+		Duration::from_millis(self.props.max_gap)
+	}
+
+	pub fn press(&mut self, time: SystemTime) {
+		// the press may have been processed after the combo should have broken
+		self.update(time);
+
+		self.combo += 1;
+		self.max_combo = self.max_combo.max(self.combo);
+		self.last_press = Some(time);
+
+		// a milestone of 0 never gets reached
+		if (self.props.milestone).is_some_and(|milestone| self.combo.checked_rem(milestone) == Some(0)) {
+			self.milestone_at = Some(time);
+		}
+	}
+
+	/// Breaks the combo if the last press was too long before `now`.
+	pub fn update(&mut self, now: SystemTime) {
+		let Some(last_press) = self.last_press else {
+			return;
+		};
+
+		if now.duration_since(last_press).unwrap_or_default() <= self.max_gap() {
+			return;
+		}
+
+		// a single press isn't much of a combo to break
+		if self.combo > 1 {
+			self.broken = Some((last_press + self.max_gap(), self.combo));
+		}
+
+		self.combo = 0;
+		self.last_press = None;
+	}
+
+	/// How much of an effect that started at `start` is left, from 1 to 0.
+	fn effect_left(&self, start: Option<SystemTime>, now: SystemTime) -> f32 {
+		let Some(start) = start else {
+			return 0.;
+		};
+
+		let age = now.duration_since(start).unwrap_or_default().as_millis() as f32;
+		(1. - age / self.props.effect_duration.max(1) as f32).max(0.)
+	}
+
+	pub fn hud_lines(&self, now: SystemTime) -> Vec<HudLine> {
+		let flash = self.effect_left(self.milestone_at, now);
+		let mut lines = vec![HudLine {
+			text: format!("{}x combo", self.combo),
+			size: 2. * self.props.font_size,
+			color: match flash > 0. {
+				true => self.props.flash_color,
+				false => self.props.color,
+			},
+			alpha: 1.,
+		}];
+
+		let break_left = self.effect_left(self.broken.map(|(time, _)| time), now);
+		let detail = match self.broken {
+			Some((_, broken_combo)) if break_left > 0. => HudLine {
+				text: format!("Break! ({}x)", broken_combo),
+				size: self.props.font_size,
+				color: self.props.break_color,
+				alpha: break_left,
+			},
+			_ => HudLine {
+				text: format!("max {}x", self.max_combo),
+				size: self.props.font_size,
+				color: DETAIL_COLOR,
+				alpha: 1.,
+			},
+		};
+
+		lines.push(detail);
+		lines
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::at;
+
+	fn counter(milestone: Option<u32>) -> ComboCounter {
+		ComboCounter::new(ComboProps {
+			max_gap: 500,
+			milestone,
+			effect_duration: 600,
+			font_size: 18.,
+			color: 0xeeeeee,
+			flash_color: 0xffec63,
+			break_color: 0xff6363,
+		})
+	}
+
+	#[test]
+	fn long_pauses_break_the_combo() {
+		let mut counter = counter(None);
+		for ms in [0, 200, 400, 900] {
+			counter.press(at(ms));
+		}
+		assert_eq!(counter.combo, 4);
+
+		// more than max_gap after the last press
+		counter.press(at(1500));
+		assert_eq!((counter.combo, counter.max_combo), (1, 4));
+		assert_eq!(counter.broken, Some((at(1400), 4)));
+
+		counter.update(at(2100));
+		assert_eq!(counter.combo, 0);
+		// a single press isn't a combo to break
+		assert_eq!(counter.broken, Some((at(1400), 4)));
+	}
+
+	#[test]
+	fn milestones_flash() {
+		let mut counter = counter(Some(3));
+		counter.press(at(0));
+		counter.press(at(100));
+		assert_eq!(counter.milestone_at, None);
+
+		counter.press(at(200));
+		assert_eq!(counter.milestone_at, Some(at(200)));
+		assert_eq!(counter.hud_lines(at(300))[0].color, 0xffec63);
+		assert_eq!(counter.hud_lines(at(800))[0].color, 0xeeeeee);
+	}
+
+	#[test]
+	fn zero_milestone_never_flashes() {
+		let mut counter = counter(Some(0));
+		for ms in [0, 100, 200] {
+			counter.press(at(ms));
+		}
+		assert_eq!(counter.milestone_at, None);
+	}
+}
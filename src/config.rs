@@ -1,3 +1,6 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -6,10 +9,16 @@ pub struct WindowProps {
 	pub transparent: bool,
 	#[serde(default = "default::yes")]
 	pub resizable: bool,
+	#[serde(default = "default::yes")]
+	pub dpi_aware: bool,
 	#[serde(default = "default::config::window::width")]
-	pub width: u32,
+	pub width: Length,
 	#[serde(default = "default::config::window::height")]
-	pub height: u32,
+	pub height: Length,
+	#[serde(default = "default::config::window::trail_length")]
+	pub trail_length: u32,
+	#[serde(default)]
+	pub resize_mode: ResizeMode,
 }
 
 impl Default for WindowProps {
@@ -17,14 +26,54 @@ impl Default for WindowProps {
 		Self {
 			transparent: default::yes(),
 			resizable: default::yes(),
+			dpi_aware: default::yes(),
 			width: default::config::window::width(),
 			height: default::config::window::height(),
+			trail_length: default::config::window::trail_length(),
+			resize_mode: ResizeMode::default(),
 		}
 	}
 }
 
+/// Window length in pixels, or `"auto"` to fit the layout.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Length {
+	Pixels(u32),
+	Auto(Auto),
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
+pub enum Auto {
+	Auto,
+}
+
+impl Length {
+	pub const AUTO: Length = Length::Auto(Auto::Auto);
+
+	pub fn resolve(self, auto: f32) -> f32 {
+		match self {
+			Length::Pixels(pixels) => pixels as f32,
+			Length::Auto(_) => auto,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResizeMode {
+	/// Keep the layout as configured
+	#[default]
+	Fixed,
+	/// Scale the whole layout uniformly to fit the window
+	ScaleToFit,
+	/// Stretch key widths and spacing to the window's width
+	StretchWidth,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum BoxPlacement {
 	Inside,
 	Outside,
@@ -40,7 +89,12 @@ pub enum ScrollDirection {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+	#[serde(default = "default::config::speed")]
 	pub speed: u32,
+	#[serde(default, deserialize_with = "check::some_positive_seconds")]
+	pub visible_duration: Option<f32>,
+	#[serde(default, deserialize_with = "check::some_positive_seconds")]
+	pub max_trail_age: Option<f32>,
 
 	#[serde(default)]
 	pub direction: ScrollDirection,
@@ -55,6 +109,11 @@ pub struct Config {
 	#[serde(default = "default::config::counter_placement")]
 	pub counter_placement: BoxPlacement,
 
+	#[serde(default)]
+	pub display_hold_times: bool,
+	#[serde(default)]
+	pub display_press_intervals: bool,
+
 	#[serde(default = "default::config::key_spacing")]
 	pub key_spacing: u32,
 	#[serde(default = "default::config::default_key_width")]
@@ -62,31 +121,178 @@ pub struct Config {
 	#[serde(default = "default::config::key_height")]
 	pub key_height: u32,
 
+	#[serde(default = "default::config::scale", deserialize_with = "check::positive_scale")]
+	pub scale: f32,
+
 	#[serde(default)]
 	pub window: WindowProps,
 
 	pub columns: Vec<ColumnProps>,
+
+	#[serde(default)]
+	pub layers: Vec<LayerProps>,
+
+	#[serde(default)]
+	pub widgets: Vec<WidgetProps>,
+
+	/// BPM meter of the presses on the columns, shown as a HUD line
+	pub stream: Option<StreamProps>,
+
+	/// Combo of presses on the columns, shown in the HUD
+	pub combo: Option<ComboProps>,
+
+	#[serde(default)]
+	pub chart: ChartProps,
+
+	#[serde(default)]
+	pub replay: ReplayProps,
+
+	#[serde(default)]
+	pub midi: MidiProps,
+
+	#[serde(default)]
+	pub keystrokes: KeystrokesProps,
+
+	#[serde(default)]
+	pub typing: TypingProps,
+
+	#[serde(default)]
+	pub tap_test: TapTestProps,
+
+	#[serde(default)]
+	pub reaction: ReactionProps,
+}
+
+impl Config {
+	/// The base columns followed by the columns of each layer.
+	pub fn column_sets(&self) -> impl Iterator<Item = &[ColumnProps]> {
+		std::iter::once(&self.columns[..]).chain(self.layers.iter().map(|layer| &layer.columns[..]))
+	}
+
+	/// Whether some text is drawn outside of the key boxes.
+	pub fn has_outside_text(&self) -> bool {
+		(self.display_keys && self.key_placement == BoxPlacement::Outside)
+			|| (self.display_counters && self.counter_placement == BoxPlacement::Outside)
+	}
 }
 
 impl Default for Config {
 	fn default() -> Self {
 		Config {
-			speed: 300,
+			speed: default::config::speed(),
+			visible_duration: None,
+			max_trail_age: None,
 			direction: ScrollDirection::default(),
 			window: WindowProps::default(),
 			display_keys: default::yes(),
 			key_placement: default::config::key_placement(),
 			display_counters: default::yes(),
 			counter_placement: default::config::counter_placement(),
+			display_hold_times: false,
+			display_press_intervals: false,
 			key_spacing: default::config::key_spacing(),
 			default_key_width: default::config::default_key_width(),
 			key_height: default::config::key_height(),
+			scale: default::config::scale(),
 			columns: vec![
 				ColumnProps::new(None, [rdev::Key::KeyD].into()),
 				ColumnProps::new(None, [rdev::Key::KeyF].into()),
 				ColumnProps::new(None, [rdev::Key::KeyJ].into()),
 				ColumnProps::new(None, [rdev::Key::KeyK].into()),
 			],
+			layers: Vec::new(),
+			widgets: Vec::new(),
+			stream: None,
+			combo: None,
+			chart: ChartProps::default(),
+			replay: ReplayProps::default(),
+			midi: MidiProps::default(),
+			keystrokes: KeystrokesProps::default(),
+			typing: TypingProps::default(),
+			tap_test: TapTestProps::default(),
+			reaction: ReactionProps::default(),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndCap {
+	#[default]
+	None,
+	/// Round the ends of each trail
+	Rounded,
+	/// Draw a line where the key was released
+	Marker,
+}
+
+/// How the keys of a multi-key column show up in its trail.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyTrails {
+	/// One trail for the whole column
+	#[default]
+	Merged,
+	/// One narrower trail per key, side by side
+	Split,
+	/// One full-width trail per key, in the key's color
+	Colored,
+}
+
+/// What increments the counter of a column.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CountMode {
+	/// Every press of any of its keys
+	#[default]
+	Key,
+	/// Every time the column goes from released to held, like its trail
+	Activation,
+	/// Every press, except keys joining a held column within `chord_window` of the last counted press
+	Chord,
+}
+
+/// Set of columns replacing the base ones while its keys are held.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerProps {
+	pub name: Option<String>,
+	pub hold: Vec<rdev::Key>,
+	pub columns: Vec<ColumnProps>,
+}
+
+/// State of other keys a column needs to react to its own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Condition {
+	#[serde(default)]
+	pub held: Vec<rdev::Key>,
+	#[serde(default)]
+	pub not_held: Vec<rdev::Key>,
+}
+
+impl Condition {
+	pub fn is_met(&self, held_keys: &HashSet<rdev::Key>) -> bool {
+		self.held.iter().all(|key| held_keys.contains(key)) && !self.not_held.iter().any(|key| held_keys.contains(key))
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnMode {
+	/// Held while one of its keys is held
+	Momentary,
+	/// Flips on and off with each press, like lock keys
+	Toggle,
+}
+
+impl ColumnMode {
+	/// Columns made only of lock keys behave like them.
+	pub fn default_for(keys: &[rdev::Key]) -> Self {
+		let is_lock_key =
+			|key: &rdev::Key| matches!(key, rdev::Key::CapsLock | rdev::Key::NumLock | rdev::Key::ScrollLock);
+
+		match !keys.is_empty() && keys.iter().all(is_lock_key) {
+			true => ColumnMode::Toggle,
+			false => ColumnMode::Momentary,
 		}
 	}
 }
@@ -97,6 +303,8 @@ pub struct ColumnProps {
 	pub keys: Vec<rdev::Key>,
 	#[serde(default = "default::column::width")]
 	pub width: u32,
+	pub speed: Option<u32>,
+	pub direction: Option<ScrollDirection>,
 	#[serde(default = "default::column::color")]
 	pub color: u32,
 	#[serde(default = "default::column::hover_color")]
@@ -105,18 +313,602 @@ pub struct ColumnProps {
 	pub border_color: u32,
 	#[serde(default = "default::column::alpha")]
 	pub alpha: f32,
+	#[serde(default)]
+	pub min_trail_length: f32,
+	#[serde(default)]
+	pub end_cap: EndCap,
+	pub marker_color: Option<u32>,
+	#[serde(default)]
+	pub key_trails: KeyTrails,
+	#[serde(default, skip_serializing_if = "HashMap::is_empty")]
+	pub key_colors: HashMap<rdev::Key, u32>,
+	#[serde(default)]
+	pub count_mode: CountMode,
+	#[serde(default = "default::column::chord_window")]
+	pub chord_window: u64,
+	pub mode: Option<ColumnMode>,
+	pub when: Option<Condition>,
+
+	pub icon: Option<PathBuf>,
+	pub pressed_icon: Option<PathBuf>,
+	#[serde(default = "default::column::icon_tint")]
+	pub icon_tint: u32,
+	#[serde(default = "default::column::pressed_icon_tint")]
+	pub pressed_icon_tint: u32,
 }
 
 impl ColumnProps {
-	fn new(name: Option<String>, keys: Vec<rdev::Key>) -> ColumnProps {
+	pub(crate) fn new(name: Option<String>, keys: Vec<rdev::Key>) -> ColumnProps {
 		ColumnProps {
 			name,
 			keys,
 			width: default::column::width(),
+			speed: None,
+			direction: None,
 			color: default::column::color(),
 			hover_color: default::column::hover_color(),
 			border_color: default::column::border_color(),
 			alpha: default::column::alpha(),
+			min_trail_length: 0.,
+			end_cap: EndCap::default(),
+			marker_color: None,
+			key_trails: KeyTrails::default(),
+			key_colors: HashMap::new(),
+			count_mode: CountMode::default(),
+			chord_window: default::column::chord_window(),
+			mode: None,
+			when: None,
+			icon: None,
+			pressed_icon: None,
+			icon_tint: default::column::icon_tint(),
+			pressed_icon_tint: default::column::pressed_icon_tint(),
+		}
+	}
+}
+
+/// Options of the stream BPM meter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamProps {
+	/// Duration the BPM is averaged over, in milliseconds
+	#[serde(default = "default::stream::smoothing_window")]
+	pub smoothing_window: u64,
+	/// Lowest BPM between two presses for them to be part of a stream
+	#[serde(default = "default::stream::threshold")]
+	pub threshold: f32,
+	#[serde(default = "default::text::font_size")]
+	pub font_size: f32,
+	#[serde(default = "default::text::color")]
+	pub color: u32,
+}
+
+/// Options of the combo counter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComboProps {
+	/// Longest pause between two presses of a combo, in milliseconds
+	#[serde(default = "default::combo::max_gap")]
+	pub max_gap: u64,
+	/// The combo flashes every time it reaches a multiple of this
+	pub milestone: Option<u32>,
+	/// Duration of the flashes and of the break effect, in milliseconds
+	#[serde(default = "default::combo::effect_duration")]
+	pub effect_duration: u64,
+	#[serde(default = "default::text::font_size")]
+	pub font_size: f32,
+	#[serde(default = "default::text::color")]
+	pub color: u32,
+	#[serde(default = "default::combo::flash_color")]
+	pub flash_color: u32,
+	#[serde(default = "default::combo::break_color")]
+	pub break_color: u32,
+}
+
+/// Options of the chart practice, for charts given with `--chart`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChartProps {
+	#[serde(default = "default::chart::start_key")]
+	pub start_key: rdev::Key,
+	#[serde(default = "default::chart::calibrate_key")]
+	pub calibrate_key: rdev::Key,
+	/// Time before the first note reaches the keys, in seconds
+	#[serde(default = "default::chart::lead_in")]
+	pub lead_in: f32,
+	/// Added to the times of the notes when judging presses, in milliseconds
+	#[serde(default)]
+	pub offset: i64,
+	/// Hit windows on each side of the notes, in milliseconds
+	#[serde(default = "default::chart::perfect_window")]
+	pub perfect_window: u64,
+	#[serde(default = "default::chart::great_window")]
+	pub great_window: u64,
+	#[serde(default = "default::chart::miss_window")]
+	pub miss_window: u64,
+	#[serde(default = "default::chart::note_color")]
+	pub note_color: u32,
+	#[serde(default = "default::chart::perfect_color")]
+	pub perfect_color: u32,
+	#[serde(default = "default::chart::great_color")]
+	pub great_color: u32,
+	#[serde(default = "default::chart::miss_color")]
+	pub miss_color: u32,
+	#[serde(default = "default::text::font_size")]
+	pub font_size: f32,
+	#[serde(default = "default::text::color")]
+	pub color: u32,
+}
+
+impl Default for ChartProps {
+	fn default() -> Self {
+		Self {
+			start_key: default::chart::start_key(),
+			calibrate_key: default::chart::calibrate_key(),
+			lead_in: default::chart::lead_in(),
+			offset: 0,
+			perfect_window: default::chart::perfect_window(),
+			great_window: default::chart::great_window(),
+			miss_window: default::chart::miss_window(),
+			note_color: default::chart::note_color(),
+			perfect_color: default::chart::perfect_color(),
+			great_color: default::chart::great_color(),
+			miss_color: default::chart::miss_color(),
+			font_size: default::text::font_size(),
+			color: default::text::color(),
+		}
+	}
+}
+
+/// Options of the replays played with `--replay`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReplayProps {
+	/// Key pressed by each lane of the replays, the first key of each column if empty
+	#[serde(default)]
+	pub keys: Vec<rdev::Key>,
+}
+
+impl ReplayProps {
+	pub fn lane_keys(&self, columns: &[ColumnProps]) -> Vec<rdev::Key> {
+		match self.keys.is_empty() {
+			true => columns
+				.iter()
+				.filter_map(|column| column.keys.first().copied())
+				.collect(),
+			false => self.keys.clone(),
+		}
+	}
+}
+
+/// Mapping between the base columns and MIDI notes, for `--export-midi` and MIDI files given to `--replay`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MidiProps {
+	/// Note of each base column, counting up from middle C if empty
+	#[serde(default)]
+	pub notes: Vec<u8>,
+	/// Channel of the exported notes, from 0 to 15
+	#[serde(default)]
+	pub channel: u8,
+	#[serde(default = "default::midi::velocity")]
+	pub velocity: u8,
+}
+
+impl Default for MidiProps {
+	fn default() -> Self {
+		Self {
+			notes: Vec::new(),
+			channel: 0,
+			velocity: default::midi::velocity(),
+		}
+	}
+}
+
+impl MidiProps {
+	pub fn column_notes(&self, n_columns: usize) -> Vec<u8> {
+		match self.notes.is_empty() {
+			true => (0..n_columns)
+				.map(|i| (default::midi::FIRST_NOTE as usize + i).min(127) as u8)
+				.collect(),
+			false => self.notes.clone(),
+		}
+	}
+}
+
+/// Options of the keystroke visualizer mode.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeystrokesProps {
+	/// How long a bubble stays after its last press, in milliseconds
+	#[serde(default = "default::keystrokes::timeout")]
+	pub timeout: u64,
+	/// How long it takes a bubble to fade out at the end of its timeout, in milliseconds
+	#[serde(default = "default::keystrokes::fade")]
+	pub fade: u64,
+	#[serde(default = "default::keystrokes::max_bubbles")]
+	pub max_bubbles: usize,
+	#[serde(default = "default::text::large_font_size")]
+	pub font_size: f32,
+	#[serde(default = "default::text::color")]
+	pub color: u32,
+	#[serde(default = "default::panel::background_color")]
+	pub background_color: u32,
+	#[serde(default = "default::panel::alpha")]
+	pub alpha: f32,
+}
+
+impl Default for KeystrokesProps {
+	fn default() -> Self {
+		Self {
+			timeout: default::keystrokes::timeout(),
+			fade: default::keystrokes::fade(),
+			max_bubbles: default::keystrokes::max_bubbles(),
+			font_size: default::text::large_font_size(),
+			color: default::text::color(),
+			background_color: default::panel::background_color(),
+			alpha: default::panel::alpha(),
+		}
+	}
+}
+
+/// Options of the typing stats mode.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TypingProps {
+	/// Duration of the rolling window for the live speed, in seconds
+	#[serde(
+		default = "default::typing::rolling_window",
+		deserialize_with = "check::positive_seconds"
+	)]
+	pub rolling_window: f32,
+	/// Pauses longer than this many seconds don't count towards the session's duration
+	#[serde(
+		default = "default::typing::idle_timeout",
+		deserialize_with = "check::positive_seconds"
+	)]
+	pub idle_timeout: f32,
+	#[serde(default = "default::text::large_font_size")]
+	pub font_size: f32,
+	#[serde(default = "default::text::color")]
+	pub color: u32,
+	/// CSV file the session's results are appended to on exit, relative to this config's directory
+	pub results_file: Option<PathBuf>,
+}
+
+impl Default for TypingProps {
+	fn default() -> Self {
+		Self {
+			rolling_window: default::typing::rolling_window(),
+			idle_timeout: default::typing::idle_timeout(),
+			font_size: default::text::large_font_size(),
+			color: default::text::color(),
+			results_file: None,
+		}
+	}
+}
+
+/// Options of the tap speed test.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TapTestProps {
+	#[serde(default = "default::tap_test::start_key")]
+	pub start_key: rdev::Key,
+	/// Duration of the test in seconds, from the first tap
+	#[serde(
+		default = "default::tap_test::duration",
+		deserialize_with = "check::positive_seconds"
+	)]
+	pub duration: f32,
+	/// Number of taps ending the test, instead of its duration
+	pub taps: Option<u32>,
+	#[serde(default = "default::text::large_font_size")]
+	pub font_size: f32,
+	#[serde(default = "default::text::color")]
+	pub color: u32,
+	#[serde(default = "default::panel::background_color")]
+	pub background_color: u32,
+	#[serde(default = "default::panel::alpha")]
+	pub alpha: f32,
+}
+
+impl Default for TapTestProps {
+	fn default() -> Self {
+		Self {
+			start_key: default::tap_test::start_key(),
+			duration: default::tap_test::duration(),
+			taps: None,
+			font_size: default::text::large_font_size(),
+			color: default::text::color(),
+			background_color: default::panel::background_color(),
+			alpha: default::panel::alpha(),
+		}
+	}
+}
+
+/// Options of the reaction time test.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReactionProps {
+	/// Keys reacting to the cue
+	#[serde(default = "default::reaction::keys")]
+	pub keys: Vec<rdev::Key>,
+	#[serde(default = "default::reaction::rounds")]
+	pub rounds: u32,
+	/// Shortest delay before the cue shows up, in seconds
+	#[serde(
+		default = "default::reaction::min_delay",
+		deserialize_with = "check::non_negative_seconds"
+	)]
+	pub min_delay: f32,
+	/// Longest delay before the cue shows up, in seconds
+	#[serde(
+		default = "default::reaction::max_delay",
+		deserialize_with = "check::non_negative_seconds"
+	)]
+	pub max_delay: f32,
+	/// Width of the distribution's buckets, in milliseconds
+	#[serde(default = "default::reaction::bucket")]
+	pub bucket: u64,
+	#[serde(default = "default::text::large_font_size")]
+	pub font_size: f32,
+	#[serde(default = "default::text::color")]
+	pub color: u32,
+	#[serde(default = "default::reaction::cue_color")]
+	pub cue_color: u32,
+	#[serde(default = "default::reaction::early_color")]
+	pub early_color: u32,
+}
+
+impl Default for ReactionProps {
+	fn default() -> Self {
+		Self {
+			keys: default::reaction::keys(),
+			rounds: default::reaction::rounds(),
+			min_delay: default::reaction::min_delay(),
+			max_delay: default::reaction::max_delay(),
+			bucket: default::reaction::bucket(),
+			font_size: default::text::large_font_size(),
+			color: default::text::color(),
+			cue_color: default::reaction::cue_color(),
+			early_color: default::reaction::early_color(),
+		}
+	}
+}
+
+impl ReactionProps {
+	/// Checks that the delays form a range, which can't be done while parsing each of them.
+	pub fn check_delays(&self) -> Result<(), String> {
+		match self.min_delay <= self.max_delay {
+			true => Ok(()),
+			false => Err(format!(
+				"reaction.min_delay ({}) can't be above reaction.max_delay ({})",
+				self.min_delay, self.max_delay
+			)),
+		}
+	}
+}
+
+/// Position and size of a widget, in the same units as the window size.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct WidgetRect {
+	pub x: u32,
+	pub y: u32,
+	pub width: u32,
+	pub height: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WidgetProps {
+	InputHistory(InputHistoryProps),
+	Dpad(DpadProps),
+	Mouse(MouseProps),
+	Apm(ApmProps),
+}
+
+impl WidgetProps {
+	pub fn rect(&self) -> WidgetRect {
+		match self {
+			Self::InputHistory(props) => props.rect,
+			Self::Dpad(props) => props.rect,
+			Self::Mouse(props) => props.rect,
+			Self::Apm(props) => props.rect,
+		}
+	}
+}
+
+/// Four keys merged into a single direction.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DirectionKeys {
+	pub up: rdev::Key,
+	pub down: rdev::Key,
+	pub left: rdev::Key,
+	pub right: rdev::Key,
+}
+
+impl DirectionKeys {
+	pub fn as_array(&self) -> [rdev::Key; 4] {
+		[self.up, self.down, self.left, self.right]
+	}
+}
+
+impl Default for DirectionKeys {
+	fn default() -> Self {
+		Self {
+			up: rdev::Key::KeyW,
+			down: rdev::Key::KeyS,
+			left: rdev::Key::KeyA,
+			right: rdev::Key::KeyD,
+		}
+	}
+}
+
+/// How opposite directions held at the same time are resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Socd {
+	/// The most recently pressed direction wins
+	LastWins,
+	/// Both directions cancel out
+	Neutral,
+	/// The direction held first wins
+	FirstWins,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputHistoryProps {
+	#[serde(flatten)]
+	pub rect: WidgetRect,
+	#[serde(default)]
+	pub directions: DirectionKeys,
+	#[serde(default = "default::input_history::socd")]
+	pub socd: Socd,
+	#[serde(default)]
+	pub buttons: Vec<ButtonProps>,
+	#[serde(default = "default::input_history::group_window")]
+	pub group_window: u64,
+	#[serde(default = "default::input_history::frame_rate")]
+	pub frame_rate: f32,
+	#[serde(default = "default::text::font_size")]
+	pub font_size: f32,
+	#[serde(default = "default::text::color")]
+	pub color: u32,
+	#[serde(default = "default::panel::background_color")]
+	pub background_color: u32,
+	#[serde(default = "default::panel::alpha")]
+	pub alpha: f32,
+	#[serde(default)]
+	pub motions: Vec<MotionProps>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DpadProps {
+	#[serde(flatten)]
+	pub rect: WidgetRect,
+	#[serde(default)]
+	pub directions: DirectionKeys,
+	#[serde(default = "default::dpad::socd")]
+	pub socd: Socd,
+	#[serde(default = "default::dpad::color")]
+	pub color: u32,
+	#[serde(default = "default::panel::background_color")]
+	pub background_color: u32,
+	#[serde(default = "default::dpad::border_color")]
+	pub border_color: u32,
+	#[serde(default = "default::panel::alpha")]
+	pub alpha: f32,
+	/// How long past directions stay visible, in milliseconds
+	#[serde(default)]
+	pub trail_duration: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MouseProps {
+	#[serde(flatten)]
+	pub rect: WidgetRect,
+	/// Size of a screen pixel in the widget
+	#[serde(default = "default::mouse::scale")]
+	pub scale: f32,
+	/// How long the cursor path stays visible, in milliseconds
+	#[serde(default = "default::mouse::trail_duration")]
+	pub trail_duration: u64,
+	#[serde(default = "default::mouse::dot_size")]
+	pub dot_size: f32,
+	#[serde(default = "default::yes")]
+	pub display_speed: bool,
+	#[serde(default = "default::mouse::color")]
+	pub color: u32,
+	#[serde(default = "default::panel::background_color")]
+	pub background_color: u32,
+	#[serde(default = "default::panel::alpha")]
+	pub alpha: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApmProps {
+	#[serde(flatten)]
+	pub rect: WidgetRect,
+	/// Keys counting as actions, all of them if empty
+	#[serde(default)]
+	pub keys: Vec<rdev::Key>,
+	#[serde(default = "default::apm::mouse_buttons")]
+	pub mouse_buttons: Vec<rdev::Button>,
+	/// Repeating the same input within this many milliseconds doesn't count towards EAPM
+	#[serde(default = "default::apm::spam_window")]
+	pub spam_window: u64,
+	/// Duration of the window the live APM is measured over, in seconds
+	#[serde(
+		default = "default::apm::rolling_window",
+		deserialize_with = "check::positive_seconds"
+	)]
+	pub rolling_window: f32,
+	/// Duration shown by the graph, in seconds
+	#[serde(
+		default = "default::apm::graph_duration",
+		deserialize_with = "check::positive_seconds"
+	)]
+	pub graph_duration: f32,
+	#[serde(default = "default::apm::graph_bars")]
+	pub graph_bars: usize,
+	/// EAPM at the top of the graph, unless it goes higher
+	#[serde(default = "default::apm::graph_max")]
+	pub graph_max: f32,
+	#[serde(default = "default::apm::graph_color")]
+	pub graph_color: u32,
+	#[serde(default = "default::text::font_size")]
+	pub font_size: f32,
+	#[serde(default = "default::text::color")]
+	pub color: u32,
+	#[serde(default = "default::panel::background_color")]
+	pub background_color: u32,
+	#[serde(default = "default::panel::alpha")]
+	pub alpha: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ButtonProps {
+	pub key: rdev::Key,
+	pub label: Option<String>,
+}
+
+/// Sequence of directions to detect in the input history, in numpad notation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MotionProps {
+	pub name: String,
+	pub sequence: Vec<u8>,
+	#[serde(default = "default::motion::window")]
+	pub window: u64,
+	#[serde(default = "default::motion::color")]
+	pub color: u32,
+}
+
+/// Checks of the values whose type alone allows too much
+mod check {
+	use serde::{de, Deserialize, Deserializer};
+
+	/// A finite scale above 0.
+	pub fn positive_scale<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+		positive(f32::deserialize(deserializer)?, "scale")
+	}
+
+	/// A finite number of seconds above 0.
+	pub fn positive_seconds<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+		positive(f32::deserialize(deserializer)?, "number of seconds")
+	}
+
+	/// Same as `positive_seconds`, for optional fields which also need `#[serde(default)]`.
+	pub fn some_positive_seconds<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f32>, D::Error> {
+		positive_seconds(deserializer).map(Some)
+	}
+
+	/// A finite number of seconds, 0 included.
+	pub fn non_negative_seconds<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+		let seconds = f32::deserialize(deserializer)?;
+
+		match seconds.is_finite() && seconds >= 0. {
+			true => Ok(seconds),
+			false => Err(de::Error::custom(format!(
+				"expected a number of seconds of at least 0, got {}",
+				seconds
+			))),
+		}
+	}
+
+	fn positive<E: de::Error>(value: f32, what: &str) -> Result<f32, E> {
+		match value.is_finite() && value > 0. {
+			true => Ok(value),
+			false => Err(E::custom(format!("expected a positive {}, got {}", what, value))),
 		}
 	}
 }
@@ -126,19 +918,56 @@ mod default {
 		true
 	}
 
+	/// Text of the widgets and of the overlays drawn over the keys
+	pub mod text {
+		pub fn font_size() -> f32 {
+			18.
+		}
+
+		/// For the modes that take the whole window
+		pub fn large_font_size() -> f32 {
+			24.
+		}
+
+		pub fn color() -> u32 {
+			0xeeeeee
+		}
+	}
+
+	/// Background of the widgets and of the panels behind text
+	pub mod panel {
+		pub fn background_color() -> u32 {
+			0x111111
+		}
+
+		pub fn alpha() -> f32 {
+			0.8
+		}
+	}
+
 	pub mod config {
 		use crate::config::BoxPlacement;
 
 		pub mod window {
-			pub fn width() -> u32 {
-				420
+			use crate::config::Length;
+
+			pub fn width() -> Length {
+				Length::Pixels(420)
+			}
+
+			pub fn height() -> Length {
+				Length::Pixels(690)
 			}
 
-			pub fn height() -> u32 {
-				690
+			pub fn trail_length() -> u32 {
+				500
 			}
 		}
 
+		pub fn speed() -> u32 {
+			300
+		}
+
 		pub fn key_placement() -> BoxPlacement {
 			BoxPlacement::Inside
 		}
@@ -158,6 +987,10 @@ mod default {
 		pub fn key_height() -> u32 {
 			100
 		}
+
+		pub fn scale() -> f32 {
+			1.
+		}
 	}
 
 	pub mod column {
@@ -180,5 +1013,277 @@ mod default {
 		pub fn alpha() -> f32 {
 			0.5
 		}
+
+		pub fn icon_tint() -> u32 {
+			0xeeeeee
+		}
+
+		pub fn pressed_icon_tint() -> u32 {
+			0xffffff
+		}
+
+		pub fn chord_window() -> u64 {
+			50
+		}
+	}
+
+	pub mod input_history {
+		use crate::config::Socd;
+
+		pub fn socd() -> Socd {
+			Socd::Neutral
+		}
+
+		pub fn group_window() -> u64 {
+			20
+		}
+
+		pub fn frame_rate() -> f32 {
+			60.
+		}
+	}
+
+	pub mod dpad {
+		use crate::config::Socd;
+
+		pub fn socd() -> Socd {
+			Socd::Neutral
+		}
+
+		pub fn color() -> u32 {
+			0x63ffec
+		}
+
+		pub fn border_color() -> u32 {
+			0xeeeeee
+		}
+	}
+
+	pub mod motion {
+		pub fn window() -> u64 {
+			300
+		}
+
+		pub fn color() -> u32 {
+			0xffec63
+		}
+	}
+
+	pub mod mouse {
+		pub fn scale() -> f32 {
+			0.25
+		}
+
+		pub fn trail_duration() -> u64 {
+			500
+		}
+
+		pub fn dot_size() -> f32 {
+			3.
+		}
+
+		pub fn color() -> u32 {
+			0x63ffec
+		}
+	}
+
+	pub mod stream {
+		pub fn smoothing_window() -> u64 {
+			1000
+		}
+
+		pub fn threshold() -> f32 {
+			150.
+		}
+	}
+
+	pub mod chart {
+		pub fn start_key() -> rdev::Key {
+			rdev::Key::Return
+		}
+
+		pub fn calibrate_key() -> rdev::Key {
+			rdev::Key::F2
+		}
+
+		pub fn lead_in() -> f32 {
+			2.
+		}
+
+		pub fn perfect_window() -> u64 {
+			40
+		}
+
+		pub fn great_window() -> u64 {
+			100
+		}
+
+		pub fn miss_window() -> u64 {
+			150
+		}
+
+		pub fn note_color() -> u32 {
+			0xffffff
+		}
+
+		pub fn perfect_color() -> u32 {
+			0xffec63
+		}
+
+		pub fn great_color() -> u32 {
+			0x63ffec
+		}
+
+		pub fn miss_color() -> u32 {
+			0xff6363
+		}
+	}
+
+	pub mod midi {
+		/// Middle C
+		pub const FIRST_NOTE: u8 = 60;
+
+		pub fn velocity() -> u8 {
+			100
+		}
+	}
+
+	pub mod combo {
+		pub fn max_gap() -> u64 {
+			500
+		}
+
+		pub fn effect_duration() -> u64 {
+			600
+		}
+
+		pub fn flash_color() -> u32 {
+			0xffec63
+		}
+
+		pub fn break_color() -> u32 {
+			0xff6363
+		}
+	}
+
+	pub mod keystrokes {
+		pub fn timeout() -> u64 {
+			1500
+		}
+
+		pub fn fade() -> u64 {
+			300
+		}
+
+		pub fn max_bubbles() -> usize {
+			5
+		}
+	}
+
+	pub mod typing {
+		pub fn rolling_window() -> f32 {
+			10.
+		}
+
+		pub fn idle_timeout() -> f32 {
+			5.
+		}
+	}
+
+	pub mod apm {
+		pub fn mouse_buttons() -> Vec<rdev::Button> {
+			vec![rdev::Button::Left, rdev::Button::Right, rdev::Button::Middle]
+		}
+
+		pub fn spam_window() -> u64 {
+			150
+		}
+
+		pub fn rolling_window() -> f32 {
+			10.
+		}
+
+		pub fn graph_duration() -> f32 {
+			60.
+		}
+
+		pub fn graph_bars() -> usize {
+			30
+		}
+
+		pub fn graph_max() -> f32 {
+			200.
+		}
+
+		pub fn graph_color() -> u32 {
+			0x63ffec
+		}
+	}
+
+	pub mod tap_test {
+		pub fn start_key() -> rdev::Key {
+			rdev::Key::Return
+		}
+
+		pub fn duration() -> f32 {
+			10.
+		}
+	}
+
+	pub mod reaction {
+		pub fn keys() -> Vec<rdev::Key> {
+			vec![rdev::Key::Space]
+		}
+
+		pub fn rounds() -> u32 {
+			5
+		}
+
+		pub fn min_delay() -> f32 {
+			1.5
+		}
+
+		pub fn max_delay() -> f32 {
+			4.
+		}
+
+		pub fn bucket() -> u64 {
+			25
+		}
+
+		pub fn cue_color() -> u32 {
+			0x63ffec
+		}
+
+		pub fn early_color() -> u32 {
+			0xff6363
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use rdev::Key;
+
+	#[test]
+	fn conditions_check_held_and_not_held_keys() {
+		let condition = Condition {
+			held: vec![Key::ShiftLeft],
+			not_held: vec![Key::ControlLeft],
+		};
+
+		let held = |keys: &[Key]| keys.iter().copied().collect::<HashSet<_>>();
+		assert!(condition.is_met(&held(&[Key::ShiftLeft])));
+		assert!(condition.is_met(&held(&[Key::ShiftLeft, Key::KeyA])));
+		assert!(!condition.is_met(&held(&[])));
+		assert!(!condition.is_met(&held(&[Key::ShiftLeft, Key::ControlLeft])));
+
+		let empty = Condition {
+			held: Vec::new(),
+			not_held: Vec::new(),
+		};
+		assert!(empty.is_met(&held(&[])));
 	}
 }
@@ -0,0 +1,100 @@
+use crate::config::{DirectionKeys, Socd};
+use crate::key::display_key;
+
+/// One of the 8 directions or neutral, as unit steps on each axis (y pointing up).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Direction {
+	pub x: i8,
+	pub y: i8,
+}
+
+impl Direction {
+	/// Direction from numpad notation, 5 being neutral and 6 forward.
+	pub fn from_numpad(n: u8) -> Option<Self> {
+		match n {
+			1..=9 => Some(Self {
+				x: (n as i8 - 1) % 3 - 1,
+				y: (n as i8 - 1) / 3 - 1,
+			}),
+			_ => None,
+		}
+	}
+
+	pub fn glyph(self) -> &'static str {
+		match (self.x, self.y) {
+			(0, 1) => display_key(rdev::Key::UpArrow),
+			(1, 0) => display_key(rdev::Key::RightArrow),
+			(0, -1) => display_key(rdev::Key::DownArrow),
+			(-1, 0) => display_key(rdev::Key::LeftArrow),
+			(1, 1) => "↗",
+			(1, -1) => "↘",
+			(-1, -1) => "↙",
+			(-1, 1) => "↖",
+			_ => "•",
+		}
+	}
+}
+
+/// Held state of four direction keys, merged into a single direction.
+#[derive(Debug, Clone)]
+pub struct DirectionInput {
+	pub keys: DirectionKeys,
+	pub socd: Socd,
+	/// Press order of up, down, left and right while they're held
+	presses: [Option<u64>; 4],
+	press_count: u64,
+}
+
+impl DirectionInput {
+	pub fn new(keys: DirectionKeys, socd: Socd) -> Self {
+		Self {
+			keys,
+			socd,
+			presses: [None; 4],
+			press_count: 0,
+		}
+	}
+
+	/// Updates the held state with a key event, returning whether the key is one of the directions.
+	pub fn set_key_pressed(&mut self, key: rdev::Key, pressed: bool) -> bool {
+		let Some(i) = self.keys.as_array().iter().position(|&k| k == key) else {
+			return false;
+		};
+
+		match pressed {
+			// key repeats don't count as new presses
+			true if self.presses[i].is_some() => (),
+			true => {
+				self.press_count += 1;
+				self.presses[i] = Some(self.press_count);
+			}
+			false => self.presses[i] = None,
+		}
+
+		true
+	}
+
+	/// Current direction, with opposite keys resolved according to the SOCD mode.
+	pub fn direction(&self) -> Direction {
+		let [up, down, left, right] = self.presses;
+
+		Direction {
+			x: self.resolve_axis(right, left),
+			y: self.resolve_axis(up, down),
+		}
+	}
+
+	fn resolve_axis(&self, positive: Option<u64>, negative: Option<u64>) -> i8 {
+		match (positive, negative) {
+			(None, None) => 0,
+			(Some(_), None) => 1,
+			(None, Some(_)) => -1,
+			(Some(positive), Some(negative)) => match self.socd {
+				Socd::Neutral => 0,
+				Socd::LastWins if positive > negative => 1,
+				Socd::FirstWins if positive < negative => 1,
+				Socd::LastWins | Socd::FirstWins => -1,
+			},
+		}
+	}
+}
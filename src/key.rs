@@ -111,3 +111,47 @@ pub fn display_key(key: rdev::Key) -> &'static str {
 		rdev::Key::Unknown(_) => "(?)",
 	}
 }
+
+/// Whether the key types a character on its own, like letters, digits, punctuation and space.
+pub fn is_character(key: rdev::Key) -> bool {
+	use rdev::Key::*;
+
+	matches!(
+		key,
+		KeyA | KeyB
+			| KeyC | KeyD
+			| KeyE | KeyF
+			| KeyG | KeyH
+			| KeyI | KeyJ
+			| KeyK | KeyL
+			| KeyM | KeyN
+			| KeyO | KeyP
+			| KeyQ | KeyR
+			| KeyS | KeyT
+			| KeyU | KeyV
+			| KeyW | KeyX
+			| KeyY | KeyZ
+			| Num0 | Num1
+			| Num2 | Num3
+			| Num4 | Num5
+			| Num6 | Num7
+			| Num8 | Num9
+			| Kp0 | Kp1
+			| Kp2 | Kp3
+			| Kp4 | Kp5
+			| Kp6 | Kp7
+			| Kp8 | Kp9
+			| KpMinus | KpPlus
+			| KpMultiply
+			| KpDivide
+			| Space | BackQuote
+			| Minus | Equal
+			| LeftBracket
+			| RightBracket
+			| SemiColon
+			| Quote | BackSlash
+			| IntlBackslash
+			| Comma | Dot
+			| Slash
+	)
+}
@@ -0,0 +1,193 @@
+//! Keystroke visualizer, showing typed shortcuts as bubbles for screencasts
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime};
+
+use glam::{vec2, Vec2};
+use loki_draw::drawer::{Drawer, RectBlueprint, TextBlueprint};
+use loki_draw::font::Font;
+use loki_draw::rect::Rect;
+use winit::keyboard::ModifiersState;
+
+use crate::config::KeystrokesProps;
+use crate::key::display_key;
+use crate::texture::ImageDrawer;
+use crate::{InputEvent, KeyEvent, Scene, ROBOTO_FONT};
+
+const BUBBLE_PADDING: f32 = 8.;
+const BUBBLE_GAP: f32 = 6.;
+
+/// Modifiers in the order they're written in, with the keys for each.
+const MODIFIERS: [(&str, &[rdev::Key]); 5] = [
+	("Ctrl", &[rdev::Key::ControlLeft, rdev::Key::ControlRight]),
+	("Shift", &[rdev::Key::ShiftLeft, rdev::Key::ShiftRight]),
+	("Alt", &[rdev::Key::Alt]),
+	("AltGr", &[rdev::Key::AltGr]),
+	("Meta", &[rdev::Key::MetaLeft, rdev::Key::MetaRight]),
+];
+
+fn is_modifier(key: rdev::Key) -> bool {
+	MODIFIERS.iter().any(|(_, keys)| keys.contains(&key))
+}
+
+struct Bubble {
+	label: String,
+	count: u32,
+	/// Time of the last press, from which the bubble starts fading
+	time: SystemTime,
+}
+
+impl Bubble {
+	fn text(&self) -> String {
+		match self.count {
+			1 => self.label.clone(),
+			count => format!("{} ×{}", self.label, count),
+		}
+	}
+}
+
+pub struct KeystrokeScene {
+	props: KeystrokesProps,
+	input_rx: mpsc::Receiver<InputEvent>,
+	default_font: Font<'static>,
+	now: SystemTime,
+	held_keys: HashSet<rdev::Key>,
+	/// Oldest first
+	bubbles: VecDeque<Bubble>,
+}
+
+impl KeystrokeScene {
+	pub fn new(input_rx: mpsc::Receiver<InputEvent>, props: KeystrokesProps) -> Self {
+		Self {
+			props,
+			input_rx,
+			default_font: Font::from_data(ROBOTO_FONT),
+			now: SystemTime::now(),
+			held_keys: HashSet::new(),
+			bubbles: VecDeque::new(),
+		}
+	}
+
+	fn handle_key_event(&mut self, key_event: KeyEvent) {
+		if !key_event.pressed {
+			self.held_keys.remove(&key_event.key);
+			return;
+		}
+
+		// key repeats aren't new keystrokes
+		if !self.held_keys.insert(key_event.key) || is_modifier(key_event.key) {
+			return;
+		}
+
+		let mut label = String::new();
+		for (name, keys) in MODIFIERS {
+			if keys.iter().any(|key| self.held_keys.contains(key)) {
+				label += name;
+				label += "+";
+			}
+		}
+		label += display_key(key_event.key);
+
+		let timeout = Duration::from_millis(self.props.timeout);
+		let last = (self.bubbles.back_mut())
+			.filter(|bubble| key_event.time.duration_since(bubble.time).is_ok_and(|dt| dt < timeout));
+
+		match last {
+			Some(bubble) if bubble.label == label => {
+				bubble.count += 1;
+				bubble.time = key_event.time;
+			}
+			_ => {
+				if self.bubbles.len() >= self.props.max_bubbles {
+					self.bubbles.pop_front();
+				}
+
+				self.bubbles.push_back(Bubble {
+					label,
+					count: 1,
+					time: key_event.time,
+				});
+			}
+		}
+	}
+
+	/// Opacity of a bubble, fading out during the last part of its timeout.
+	fn bubble_alpha(&self, bubble: &Bubble) -> f32 {
+		let age = self.now.duration_since(bubble.time).unwrap_or_default().as_secs_f32();
+		let timeout = self.props.timeout as f32 / 1000.;
+		let fade = (self.props.fade as f32 / 1000.).min(timeout);
+
+		((timeout - age) / fade.max(f32::EPSILON)).clamp(0., 1.)
+	}
+}
+
+impl Scene for KeystrokeScene {
+	fn update(&mut self) {
+		while let Ok(input_event) = self.input_rx.try_recv() {
+			if let InputEvent::Key(key_event) = input_event {
+				self.handle_key_event(key_event);
+			}
+		}
+
+		self.now = SystemTime::now();
+
+		let timeout = Duration::from_millis(self.props.timeout);
+		while (self.bubbles.front())
+			.is_some_and(|bubble| self.now.duration_since(bubble.time).unwrap_or_default() >= timeout)
+		{
+			self.bubbles.pop_front();
+		}
+	}
+
+	fn resize(&mut self, _viewport: Vec2) {}
+
+	fn inapp_key_event(&mut self, _event: winit::event::KeyEvent, _modifiers: ModifiersState) {}
+
+	fn draw(&self, viewport: Vec2, drawer: &mut impl Drawer, _image_drawer: &mut impl ImageDrawer) {
+		drawer.clear();
+		drawer.begin_frame();
+		{
+			// newest bubble at the bottom, older ones stacking up above it
+			let mut bottom = viewport.y - BUBBLE_GAP;
+
+			for bubble in self.bubbles.iter().rev() {
+				let text = bubble.text();
+				let alpha = self.bubble_alpha(bubble);
+
+				let mut text_blueprint = TextBlueprint {
+					text: &text,
+					x: 0.,
+					y: 0.,
+					font: &self.default_font,
+					size: self.props.font_size,
+					col: self.props.color,
+					alpha,
+				};
+
+				let size = vec2(text_blueprint.text_width(), text_blueprint.text_height()) + 2. * BUBBLE_PADDING;
+				let top_left = vec2((viewport.x - size.x) / 2., bottom - size.y);
+				if top_left.y < 0. {
+					break;
+				}
+
+				drawer.draw_rect(&RectBlueprint {
+					rect: Rect::new(top_left.x, top_left.y, size.x, size.y),
+					color: self.props.background_color,
+					border_color: self.props.background_color,
+					border_width: 0.,
+					corner_radius: BUBBLE_PADDING,
+					borders: [false, false, false, false],
+					alpha: alpha * self.props.alpha,
+				});
+
+				text_blueprint.x = top_left.x + BUBBLE_PADDING;
+				text_blueprint.y = top_left.y + BUBBLE_PADDING;
+				drawer.draw_text(&text_blueprint);
+
+				bottom = top_left.y - BUBBLE_GAP;
+			}
+		}
+		drawer.end_frame();
+	}
+}
@@ -1,8 +1,29 @@
 use std::ops::{Deref, DerefMut};
 
-use glam::Vec2;
+use glam::{vec2, Vec2};
 use loki_draw::rect::Rect;
 
+use crate::config::{Config, ScrollDirection};
+
+/// Distance between the key boxes and the edge of the window
+const KEY_MARGIN: f32 = 30.;
+pub const SMOL_FONT_SIZE: f32 = 20.;
+pub const BOTTOM_KEY_TEXT_GAP: f32 = 5.;
+
+/// Color of the secondary lines of text, in the HUD and the stats screens
+pub const DETAIL_COLOR: u32 = 0x999999;
+/// Distance between the text of the stats screens and the edge of the window or of their panel
+pub const TEXT_PADDING: f32 = 10.;
+/// Distance between the HUD and the edge of the window
+pub const HUD_PADDING: f32 = 5.;
+/// Vertical gap between two lines of text
+pub const LINE_GAP: f32 = 4.;
+
+/// The key margin, grown if needed to fit the texts drawn outside of the key boxes.
+pub fn key_margin(config: &Config) -> f32 {
+	KEY_MARGIN.max(outside_text_height(config))
+}
+
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Anchor(Vec2);
 
@@ -64,3 +85,54 @@ impl OwoRect {
 		}
 	}
 }
+
+/// Key boxes of the overlay, laid out for the current viewport.
+#[derive(Debug, Clone, Default)]
+pub struct KeyLayout {
+	pub columns: Vec<ColumnLayout>,
+	/// How much the configured sizes are scaled on each axis
+	pub scale: Vec2,
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnLayout {
+	pub key_rect: OwoRect,
+	pub direction: ScrollDirection,
+	/// Scrolling speed of the trail in pixels per second
+	pub speed: f32,
+	/// Length past which the trail isn't drawn anymore
+	pub trail_length: f32,
+}
+
+/// Room taken by the texts below the key boxes, or above them when scrolling down.
+fn outside_text_height(config: &Config) -> f32 {
+	match config.has_outside_text() {
+		true => BOTTOM_KEY_TEXT_GAP + SMOL_FONT_SIZE,
+		false => 0.,
+	}
+}
+
+/// Window size fitting the widest set of columns side by side, with `window.trail_length` pixels of trail,
+/// and all the widgets.
+pub fn auto_window_size(config: &Config) -> Vec2 {
+	// keys are half of `key_spacing` apart, like in the overlay
+	let content_width = (config.column_sets())
+		.map(|columns| {
+			let n_columns = columns.len() as f32;
+			config.default_key_width as f32 * n_columns + config.key_spacing as f32 / 2. * (n_columns - 1.).max(0.)
+		})
+		.fold(0., f32::max);
+
+	let content_height = config.key_height as f32 + config.window.trail_length as f32;
+
+	let columns_size = vec2(
+		content_width + 2. * config.key_spacing as f32,
+		content_height + key_margin(config),
+	);
+
+	// widgets are placed freely, so just make sure they fit
+	(config.widgets.iter()).fold(columns_size, |size, widget| {
+		let rect = widget.rect();
+		size.max(vec2((rect.x + rect.width) as f32, (rect.y + rect.height) as f32))
+	})
+}
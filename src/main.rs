@@ -8,41 +8,104 @@ use std::{fs, io, thread};
 
 use app::OwOverlayApp;
 use app_frame::AppFrame;
-use clap::Parser;
-use config::{BoxPlacement, ColumnProps, Config, ScrollDirection};
+use chart::{Chart, ChartPractice};
+use clap::{Parser, ValueEnum};
+use combo::ComboCounter;
+use config::{
+	BoxPlacement, ColumnMode, ColumnProps, Config, CountMode, EndCap, KeyTrails, Length, ResizeMode, ScrollDirection,
+};
 use glam::{vec2, Vec2};
 use key::display_key;
-use layout::{Anchor, OwoRect};
+use keystrokes::KeystrokeScene;
+use layout::{Anchor, ColumnLayout, KeyLayout, OwoRect, BOTTOM_KEY_TEXT_GAP, HUD_PADDING, LINE_GAP, SMOL_FONT_SIZE};
 use loki_draw::drawer::{Drawer, RectBlueprint, TextBlueprint};
 use loki_draw::font::Font;
 use loki_draw::rect::Rect;
-use winit::dpi::PhysicalSize;
+use midi::MidiRecorder;
+use playback::spawn_playback;
+use reaction::ReactionScene;
+use records::Records;
+use stream::StreamMeter;
+use tap_test::TapTestScene;
+use texture::{Image, ImageBlueprint, ImageDrawer};
+use trail::Trail;
+use typing::TypingScene;
+use widget::Widget;
+use winit::dpi::{LogicalSize, PhysicalSize};
 use winit::event::ElementState;
 use winit::keyboard::ModifiersState;
 use winit::window::WindowBuilder;
 
 mod app;
 mod app_frame;
+mod chart;
+mod combo;
 mod config;
+mod direction;
 mod key;
+mod keystrokes;
 mod layout;
+mod midi;
+mod osr;
+mod playback;
+mod reaction;
+mod records;
+mod stream;
+mod tap_test;
+mod texture;
+mod trail;
+mod typing;
+mod widget;
 
 const ROBOTO_FONT: &[u8] = include_bytes!("../assets/Roboto-Regular.ttf");
 
+const KEY_BORDER_WIDTH: f32 = 8.;
+const BIG_FONT_SIZE: f32 = 25.;
+const CENTER_TEXT_GAP: f32 = 2.;
+const RELEASE_MARKER_THICKNESS: f32 = 3.;
+const TRAIL_LABEL_FONT_SIZE: f32 = 14.;
+const TRAIL_LABEL_PADDING: f32 = 2.;
+const HOLD_LABEL_COLOR: u32 = 0xeeeeee;
+const INTERVAL_LABEL_COLOR: u32 = 0x999999;
+const NOTE_HEIGHT: f32 = 12.;
+
+/// Line of text in the HUD, at the far end of the lanes.
+pub struct HudLine {
+	pub text: String,
+	pub size: f32,
+	pub color: u32,
+	pub alpha: f32,
+}
+
 pub trait Scene {
 	fn update(&mut self);
+	fn resize(&mut self, viewport: Vec2);
 	fn inapp_key_event(&mut self, event: winit::event::KeyEvent, modifiers: ModifiersState);
-	fn draw(&self, viewport: Vec2, drawer: &mut impl Drawer);
+	/// Called once when the app exits, however it does.
+	fn exit(&mut self) {}
+	fn draw(&self, viewport: Vec2, drawer: &mut impl Drawer, image_drawer: &mut impl ImageDrawer);
 }
 
 #[derive(Clone)]
 struct KeyColumn {
 	pub name: String,
+	/// Index of the column set this column belongs to, 0 being the base one
+	pub layer: usize,
 	pub count: u64,
+	/// Time of the last press that incremented `count`
+	pub last_counted_press: Option<SystemTime>,
+	pub mode: ColumnMode,
+	/// Whether the column is held, or latched on for toggle columns
 	pub pressed: bool,
 	pub pressed_keys: HashMap<rdev::Key, bool>,
+	/// Latch state of each key of a toggle column
+	pub toggled_keys: HashMap<rdev::Key, bool>,
+	pub key_counts: HashMap<rdev::Key, u64>,
 	pub props: ColumnProps,
-	pub times: VecDeque<SystemTime>,
+	pub trail: Trail,
+	pub key_trails: HashMap<rdev::Key, Trail>,
+	pub icon: Option<Image>,
+	pub pressed_icon: Option<Image>,
 }
 
 impl fmt::Display for KeyColumn {
@@ -54,12 +117,12 @@ impl fmt::Display for KeyColumn {
 			write!(f, "{:?}", key)?;
 		}
 
-		write!(f, "] {} (#T={})", self.count, self.times.len())
+		write!(f, "] {} (#T={})", self.count, self.trail.len())
 	}
 }
 
 impl KeyColumn {
-	pub fn new(props: ColumnProps) -> Self {
+	pub fn new(props: ColumnProps, layer: usize) -> Result<Self, Box<dyn Error>> {
 		let name = match &props.name {
 			Some(name) => name.clone(),
 			None => {
@@ -73,15 +136,39 @@ impl KeyColumn {
 			}
 		};
 
-		let pressed_keys = props.keys.iter().copied().map(|key| (key, false)).collect();
+		let pressed_keys: HashMap<_, _> = props.keys.iter().copied().map(|key| (key, false)).collect();
+		let toggled_keys = pressed_keys.clone();
+		let key_counts = props.keys.iter().copied().map(|key| (key, 0)).collect();
+		let key_trails = props.keys.iter().copied().map(|key| (key, Trail::new())).collect();
 
-		Self {
+		let icon = props.icon.as_deref().map(Image::load).transpose()?;
+		let pressed_icon = props.pressed_icon.as_deref().map(Image::load).transpose()?;
+
+		Ok(Self {
 			name,
+			layer,
 			count: 0,
+			last_counted_press: None,
+			mode: props.mode.unwrap_or_else(|| ColumnMode::default_for(&props.keys)),
 			pressed: false,
 			pressed_keys,
+			toggled_keys,
+			key_counts,
 			props,
-			times: VecDeque::with_capacity(1024),
+			trail: Trail::new(),
+			key_trails,
+			icon,
+			pressed_icon,
+		})
+	}
+
+	/// Icon to draw in the key box for the current state, if the column has one.
+	pub fn current_icon(&self) -> Option<(&Image, u32)> {
+		match self.pressed {
+			true => {
+				(self.pressed_icon.as_ref().or(self.icon.as_ref())).map(|icon| (icon, self.props.pressed_icon_tint))
+			}
+			false => self.icon.as_ref().map(|icon| (icon, self.props.icon_tint)),
 		}
 	}
 
@@ -96,25 +183,112 @@ impl KeyColumn {
 
 		*pressed_key = event.pressed;
 
-		if event.pressed {
-			self.count += 1;
+		let prev_pressed = self.pressed;
+
+		match self.mode {
+			ColumnMode::Momentary => {
+				if let Some(key_trail) = self.key_trails.get_mut(&event.key) {
+					key_trail.push(event.time);
+				}
+
+				self.pressed = self.pressed_keys.values().any(|&v| v);
+			}
+			ColumnMode::Toggle => {
+				// releases don't change anything, presses flip the latch
+				if !event.pressed {
+					return;
+				}
+
+				if let Some(toggled_key) = self.toggled_keys.get_mut(&event.key) {
+					*toggled_key = !*toggled_key;
+				}
+
+				if let Some(key_trail) = self.key_trails.get_mut(&event.key) {
+					key_trail.push(event.time);
+				}
+
+				self.pressed = self.toggled_keys.values().any(|&v| v);
+			}
 		}
 
-		let prev_pressed = self.pressed;
-		self.pressed = self.pressed_keys.values().any(|&v| v);
+		if event.pressed {
+			*self.key_counts.entry(event.key).or_default() += 1;
+
+			let counted = match self.props.count_mode {
+				CountMode::Key => true,
+				CountMode::Activation => !prev_pressed,
+				// keys joining a held column shortly after the last counted press are part of the same chord
+				CountMode::Chord => {
+					let chord_window = Duration::from_millis(self.props.chord_window);
+					let in_chord = self.last_counted_press.is_some_and(|last_press| {
+						event.time.duration_since(last_press).unwrap_or_default() <= chord_window
+					});
+
+					!(prev_pressed && in_chord)
+				}
+			};
+
+			if counted {
+				self.count += 1;
+				self.last_counted_press = Some(event.time);
+			}
+		}
 
 		if prev_pressed == self.pressed {
 			return;
 		}
 
-		if self.times.len() >= 1024 {
-			self.times.pop_back();
+		self.trail.push(event.time);
+	}
+
+	/// Whether a key of the column is held, or latched on for toggle columns.
+	pub fn key_active(&self, key: rdev::Key) -> bool {
+		let keys = match self.mode {
+			ColumnMode::Momentary => &self.pressed_keys,
+			ColumnMode::Toggle => &self.toggled_keys,
+		};
+
+		keys.get(&key).copied().unwrap_or_default()
+	}
+
+	pub fn counter_text(&self) -> String {
+		match self.props.key_trails {
+			KeyTrails::Merged => self.count.to_string(),
+			KeyTrails::Split | KeyTrails::Colored => (self.props.keys.iter())
+				.map(|key| self.key_counts.get(key).copied().unwrap_or_default().to_string())
+				.collect::<Vec<_>>()
+				.join("/"),
 		}
+	}
 
-		self.times.push_front(event.time);
+	pub fn key_color(&self, key: rdev::Key) -> u32 {
+		self.props.key_colors.get(&key).copied().unwrap_or(self.props.color)
 	}
 }
 
+/// Text describing a trail segment, spanning from `start` to `end` pixels away from the key.
+struct TrailLabel {
+	text: String,
+	/// Horizontal center of the label, shared by the lanes drawn on top of each other
+	x: f32,
+	start: f32,
+	end: f32,
+	color: u32,
+	/// Whether the label can be moved away from what it describes to avoid overlapping
+	movable: bool,
+}
+
+/// Part of a column one trail is drawn in.
+struct Lane<'a> {
+	column: &'a KeyColumn,
+	layout: &'a ColumnLayout,
+	trail: &'a Trail,
+	pressed: bool,
+	x: f32,
+	width: f32,
+	color: u32,
+}
+
 #[derive(Debug, Clone)]
 struct KeyEvent {
 	pub key: rdev::Key,
@@ -122,11 +296,39 @@ struct KeyEvent {
 	pub time: SystemTime,
 }
 
+/// Everything the global listener forwards to the scene.
+#[derive(Debug, Clone)]
+enum InputEvent {
+	Key(KeyEvent),
+	/// Cursor position in screen pixels
+	MouseMove {
+		pos: Vec2,
+		time: SystemTime,
+	},
+	MouseButton {
+		button: rdev::Button,
+		pressed: bool,
+		time: SystemTime,
+	},
+}
+
 struct KeyOverlayScene {
 	columns: Vec<KeyColumn>,
-	key_column_map: HashMap<rdev::Key, usize>,
+	/// Every column each key is part of, whatever their layer or condition
+	key_column_map: HashMap<rdev::Key, Vec<usize>>,
+	/// Keys to hold to activate each layer, in order of priority
+	layers: Vec<Vec<rdev::Key>>,
+	active_layer: usize,
+	held_keys: HashSet<rdev::Key>,
+	widgets: Vec<Widget>,
+	stream: Option<StreamMeter>,
+	combo: Option<ComboCounter>,
+	/// Chart being practiced, whose notes scroll towards the base columns
+	chart: Option<ChartPractice>,
+	/// Records the base columns into a MIDI file
+	midi_recorder: Option<MidiRecorder>,
 	default_font: Font<'static>,
-	keyboard_rx: mpsc::Receiver<KeyEvent>,
+	input_rx: mpsc::Receiver<InputEvent>,
 	now: SystemTime,
 
 	debug_mode: bool,
@@ -135,30 +337,41 @@ struct KeyOverlayScene {
 	debug_texts: Vec<String>,
 
 	speed: f32,
+	visible_duration: Option<f32>,
+	max_trail_age: Option<f32>,
 	direction: ScrollDirection,
 	display_keys: bool,
 	key_placement: BoxPlacement,
 	display_counters: bool,
 	counter_placement: BoxPlacement,
+	display_hold_times: bool,
+	display_press_intervals: bool,
 	key_spacing: f32,
 	default_key_width: f32,
 	key_height: f32,
+	key_margin: f32,
+
+	resize_mode: ResizeMode,
+	base_viewport: Vec2,
+	layout: KeyLayout,
 }
 
 impl KeyOverlayScene {
 	fn new(
-		keyboard_rx: mpsc::Receiver<KeyEvent>,
+		input_rx: mpsc::Receiver<InputEvent>,
 		config: &Config,
+		window_size: Vec2,
 		key_columns: impl IntoIterator<Item = KeyColumn>,
+		widgets: Vec<Widget>,
 	) -> Self {
-		let mut key_column_map = HashMap::new();
+		let mut key_column_map = HashMap::<_, Vec<_>>::new();
 
 		let columns = key_columns
 			.into_iter()
 			.enumerate()
 			.map(|(i, kc)| {
 				for &key in &kc.props.keys {
-					key_column_map.insert(key, i);
+					key_column_map.entry(key).or_default().push(i);
 				}
 
 				kc
@@ -168,8 +381,16 @@ impl KeyOverlayScene {
 		Self {
 			columns,
 			key_column_map,
+			layers: config.layers.iter().map(|layer| layer.hold.clone()).collect(),
+			active_layer: 0,
+			held_keys: HashSet::new(),
+			widgets,
+			stream: config.stream.clone().map(StreamMeter::new),
+			combo: config.combo.clone().map(ComboCounter::new),
+			chart: None,
+			midi_recorder: None,
 			default_font: Font::from_data(ROBOTO_FONT),
-			keyboard_rx,
+			input_rx,
 			now: SystemTime::now(),
 
 			debug_mode: false,
@@ -178,38 +399,494 @@ impl KeyOverlayScene {
 			debug_texts: Vec::new(),
 
 			speed: config.speed as f32,
+			visible_duration: config.visible_duration,
+			max_trail_age: config.max_trail_age,
 			direction: config.direction,
 			display_keys: config.display_keys,
 			key_placement: config.key_placement,
 			display_counters: config.display_counters,
 			counter_placement: config.counter_placement,
+			display_hold_times: config.display_hold_times,
+			display_press_intervals: config.display_press_intervals,
 			key_spacing: config.key_spacing as f32,
 			default_key_width: config.default_key_width as f32,
 			key_height: config.key_height as f32,
+			key_margin: layout::key_margin(config),
+
+			resize_mode: config.window.resize_mode,
+			base_viewport: window_size,
+			layout: KeyLayout::default(),
 		}
 	}
 
+	fn compute_layout(&self, viewport: Vec2) -> KeyLayout {
+		let scale = match self.resize_mode {
+			ResizeMode::Fixed => Vec2::ONE,
+			ResizeMode::ScaleToFit => Vec2::splat((viewport / self.base_viewport).min_element()),
+			ResizeMode::StretchWidth => vec2(viewport.x / self.base_viewport.x, 1.),
+		};
+
+		let key_width = self.default_key_width * scale.x;
+		// keys are half of `key_spacing` apart
+		let spacing = self.key_spacing / 2. * scale.x;
+
+		// each layer is centered on its own
+		let mut layer_columns = vec![0; self.layers.len() + 1];
+		for column in &self.columns {
+			layer_columns[column.layer] += 1;
+		}
+
+		let mut layer_x = (layer_columns.iter())
+			.map(|&n_columns| {
+				let total_width = n_columns as f32 * (key_width + spacing) - spacing;
+				(viewport.x - total_width) / 2.
+			})
+			.collect::<Vec<_>>();
+
+		// with a visible duration, trails take exactly that long to scroll through the lanes
+		let lane_length = viewport.y - (self.key_margin + self.key_height) * scale.y;
+		let global_speed = match self.visible_duration {
+			Some(visible_duration) => lane_length.max(0.) / visible_duration,
+			None => self.speed * scale.y,
+		};
+
+		let columns = (self.columns.iter())
+			.map(|column| {
+				let x = &mut layer_x[column.layer];
+				let direction = column.props.direction.unwrap_or(self.direction);
+
+				let (key_y, origin) = match direction {
+					ScrollDirection::Up => (viewport.y - self.key_margin * scale.y, Anchor::BC),
+					ScrollDirection::Down => (self.key_margin * scale.y, Anchor::TC),
+				};
+
+				let key_rect = OwoRect {
+					pos: vec2(*x + key_width / 2., key_y),
+					size: vec2(key_width, self.key_height * scale.y),
+					origin,
+				};
+
+				*x += key_width + spacing;
+
+				let speed = match column.props.speed {
+					Some(speed) => speed as f32 * scale.y,
+					None => global_speed,
+				};
+
+				// trails older than the max age get cut off, however big the window is
+				let trail_length = match self.max_trail_age {
+					Some(max_trail_age) => (max_trail_age * speed).min(viewport.y),
+					None => viewport.y,
+				};
+
+				ColumnLayout {
+					key_rect,
+					direction,
+					speed,
+					trail_length,
+				}
+			})
+			.collect();
+
+		KeyLayout { columns, scale }
+	}
+
+	/// Draws the segments of a trail in its lane and collects their labels.
+	/// Returns how many rectangles were drawn.
+	fn draw_trail(&self, drawer: &mut impl Drawer, lane: &Lane, trail_labels: &mut Vec<TrailLabel>) -> usize {
+		let props = &lane.column.props;
+		let direction = lane.layout.direction;
+		let speed = lane.layout.speed;
+		let trail_length = lane.layout.trail_length;
+
+		let base_pos = vec2(
+			lane.x,
+			lane.layout
+				.key_rect
+				.anchor(match direction {
+					ScrollDirection::Up => Anchor::TL,
+					ScrollDirection::Down => Anchor::BL,
+				})
+				.y,
+		);
+		let label_x = lane.x + lane.width / 2.;
+
+		let min_trail_length = props.min_trail_length * self.layout.scale.y;
+		let mut newer_press: Option<(SystemTime, f32)> = None;
+		let mut drawn_rects = 0;
+
+		for (time, prev_time) in lane.trail.segments(lane.pressed, self.now) {
+			let this_time = self.duration_since_now(time);
+			let prev_time_since_now = self.duration_since_now(prev_time);
+			let now_time = self.duration_since_now(self.now);
+
+			let y_start = (this_time - now_time).as_secs_f32() * speed;
+			let y_end = (prev_time_since_now - now_time).as_secs_f32() * speed;
+
+			// stop drawing rectangles once off-screen or too old
+			if y_end >= trail_length {
+				break;
+			}
+
+			// clamp coordinates to avoid floating point glitches
+			let y_start = y_start.clamp(0.0, trail_length);
+			let y_end = y_end.clamp(0.0, trail_length);
+
+			// short taps stretch towards the key, so their press stays where it happened
+			let y_end = y_end.min(y_start - min_trail_length).max(0.);
+
+			let rect = {
+				let (y, h) = match direction {
+					ScrollDirection::Up => (-y_start, y_start - y_end),
+					ScrollDirection::Down => (y_start, y_end - y_start),
+				};
+
+				Rect {
+					x: base_pos.x,
+					y: base_pos.y + y,
+					w: lane.width,
+					h,
+				}
+			};
+
+			drawer.draw_rect(&RectBlueprint {
+				rect,
+				color: lane.color,
+				border_color: 0x000000,
+				border_width: 0.,
+				corner_radius: match props.end_cap {
+					EndCap::Rounded => rect.w.min(rect.h.abs()) / 2.,
+					EndCap::None | EndCap::Marker => 0.,
+				},
+				borders: [false, false, false, false],
+				alpha: props.alpha,
+			});
+			drawn_rects += 1;
+
+			// the segment that's still held has no release to mark yet
+			let released = prev_time != self.now;
+
+			if props.end_cap == EndCap::Marker && released {
+				let thickness = (RELEASE_MARKER_THICKNESS * self.layout.scale.y).min(y_start - y_end);
+				let y = match direction {
+					ScrollDirection::Up => base_pos.y - y_end - thickness,
+					ScrollDirection::Down => base_pos.y + y_end,
+				};
+
+				drawer.draw_rect(&RectBlueprint {
+					rect: Rect::new(base_pos.x, y, lane.width, thickness),
+					color: props.marker_color.unwrap_or(props.border_color),
+					border_color: 0x000000,
+					border_width: 0.,
+					corner_radius: 0.,
+					borders: [false, false, false, false],
+					alpha: 1.,
+				});
+				drawn_rects += 1;
+			}
+
+			if let (true, Some((newer_press_time, newer_press_y))) = (self.display_press_intervals, newer_press) {
+				let interval = newer_press_time.duration_since(time).unwrap_or_default();
+				trail_labels.push(TrailLabel {
+					text: format!("+{}ms", interval.as_millis()),
+					x: label_x,
+					start: newer_press_y,
+					end: y_end,
+					color: INTERVAL_LABEL_COLOR,
+					movable: false,
+				});
+			}
+
+			if self.display_hold_times {
+				let hold = prev_time.duration_since(time).unwrap_or_default();
+				trail_labels.push(TrailLabel {
+					text: format!("{}ms", hold.as_millis()),
+					x: label_x,
+					start: y_end,
+					end: y_start,
+					color: HOLD_LABEL_COLOR,
+					movable: true,
+				});
+			}
+
+			newer_press = Some((time, y_start));
+		}
+
+		drawn_rects
+	}
+
+	/// Draws trail labels, moving them away from the key when they'd overlap the previous one at the same x.
+	/// Returns how many texts were drawn.
+	fn draw_trail_labels(
+		&self,
+		drawer: &mut impl Drawer,
+		column_layout: &ColumnLayout,
+		trail_labels: &mut [TrailLabel],
+	) -> usize {
+		let text_scale = self.layout.scale.y;
+		let direction = column_layout.direction;
+		let base_y = column_layout.key_rect.anchor(match direction {
+			ScrollDirection::Up => Anchor::TL,
+			ScrollDirection::Down => Anchor::BL,
+		});
+
+		// lanes drawn on top of each other share their labels' x, so they have to dodge each other too
+		trail_labels.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.start.total_cmp(&b.start)));
+
+		let mut drawn_texts = 0;
+		let mut current_x = None;
+		let mut free_from = 0.0;
+
+		for label in trail_labels.iter() {
+			if current_x != Some(label.x) {
+				current_x = Some(label.x);
+				free_from = 0.0;
+			}
+
+			let mut text = TextBlueprint {
+				text: &label.text,
+				x: 0.,
+				y: 0.,
+				font: &self.default_font,
+				size: TRAIL_LABEL_FONT_SIZE * text_scale,
+				col: label.color,
+				alpha: 1.,
+			};
+
+			let (text_width, text_height) = (text.text_width(), text.text_height());
+			let padding = TRAIL_LABEL_PADDING * text_scale;
+
+			// centered on what it describes, or right past it if it doesn't fit
+			let mut near = match label.end - label.start >= text_height + 2. * padding {
+				true => (label.start + label.end - text_height) / 2.,
+				false if label.movable => label.end + padding,
+				false => continue,
+			};
+
+			if near < free_from {
+				if !label.movable {
+					continue;
+				}
+
+				near = free_from;
+			}
+
+			if near + text_height > column_layout.trail_length {
+				continue;
+			}
+
+			free_from = near + text_height + padding;
+
+			text.x = label.x - text_width / 2.;
+			text.y = match direction {
+				ScrollDirection::Up => base_y.y - near - text_height,
+				ScrollDirection::Down => base_y.y + near,
+			};
+
+			drawer.draw_text(&text);
+			drawn_texts += 1;
+		}
+
+		drawn_texts
+	}
+
 	fn duration_since_now(&self, time: SystemTime) -> Duration {
-		let time = time.duration_since(SystemTime::UNIX_EPOCH).unwrap();
-		let now = self.now.duration_since(SystemTime::UNIX_EPOCH).unwrap();
+		// past times give an error, which holds how long ago they were
+		match time.duration_since(self.now) {
+			Ok(duration) => duration,
+			Err(err) => err.duration(),
+		}
+	}
 
-		if time >= now {
-			time - now
-		} else {
-			now - time
+	/// Draws the notes of the chart scrolling towards their key, returning how many rects were drawn.
+	fn draw_chart_notes(&self, drawer: &mut impl Drawer) -> usize {
+		let Some(chart) = &self.chart else {
+			return 0;
+		};
+
+		// the chart is played on the base columns
+		if self.active_layer != 0 {
+			return 0;
+		}
+
+		let note_height = NOTE_HEIGHT * self.layout.scale.y;
+		let mut drawn_rects = 0;
+
+		for (lane, time_left) in chart.visible_notes(self.now) {
+			let Some(column_layout) = self.layout.columns.get(lane) else {
+				continue;
+			};
+
+			// notes late enough to still be hit stay on the key
+			let distance = time_left.max(0.) * column_layout.speed;
+			if distance >= column_layout.trail_length {
+				continue;
+			}
+
+			let key_rect = &column_layout.key_rect;
+			let y = match column_layout.direction {
+				ScrollDirection::Up => key_rect.anchor(Anchor::TL).y - distance - note_height,
+				ScrollDirection::Down => key_rect.anchor(Anchor::BL).y + distance,
+			};
+
+			drawer.draw_rect(&RectBlueprint {
+				rect: Rect::new(key_rect.top_left().x, y, key_rect.size.x, note_height),
+				color: chart.props.note_color,
+				border_color: chart.props.note_color,
+				border_width: 0.,
+				corner_radius: note_height / 2.,
+				borders: [false, false, false, false],
+				alpha: 1.,
+			});
+			drawn_rects += 1;
+		}
+
+		drawn_rects
+	}
+
+	/// Draws the HUD lines at the far end of the lanes, returning how many texts were drawn.
+	fn draw_hud(&self, viewport: Vec2, drawer: &mut impl Drawer) -> usize {
+		let stream_lines = self.stream.iter().map(|stream| HudLine {
+			text: stream.hud_line(self.now),
+			size: stream.props.font_size,
+			color: stream.props.color,
+			alpha: 1.,
+		});
+		let combo_lines = self.combo.iter().flat_map(|combo| combo.hud_lines(self.now));
+		let chart_lines = self.chart.iter().flat_map(|chart| chart.hud_lines(self.now));
+		let hud_lines = stream_lines.chain(combo_lines).chain(chart_lines).collect::<Vec<_>>();
+
+		let text_scale = self.layout.scale.y;
+		let texts = (hud_lines.iter())
+			.map(|line| TextBlueprint {
+				text: &line.text,
+				x: HUD_PADDING,
+				y: 0.,
+				font: &self.default_font,
+				size: line.size * text_scale,
+				col: line.color,
+				alpha: line.alpha,
+			})
+			.collect::<Vec<_>>();
+
+		// the HUD sits at the far end of the leftmost lane of the active layer, away from its key
+		let direction = (self.columns.iter().zip(&self.layout.columns))
+			.find(|(column, _)| column.layer == self.active_layer)
+			.map_or(self.direction, |(_, column_layout)| column_layout.direction);
+
+		let total_height = texts.iter().map(|text| text.text_height() + LINE_GAP).sum::<f32>();
+		let mut y = match direction {
+			ScrollDirection::Up => HUD_PADDING,
+			ScrollDirection::Down => viewport.y - HUD_PADDING - total_height,
+		};
+
+		for mut text in texts {
+			text.y = y;
+			drawer.draw_text(&text);
+			y += text.text_height() + LINE_GAP;
 		}
+
+		hud_lines.len()
+	}
+
+	/// The first layer whose keys are all held, or the base one.
+	fn find_active_layer(&self) -> usize {
+		(self.layers.iter())
+			.position(|hold| !hold.is_empty() && hold.iter().all(|key| self.held_keys.contains(key)))
+			.map_or(0, |i| i + 1)
 	}
 
-	fn column_mut(&mut self, key: rdev::Key) -> Option<&mut KeyColumn> {
-		self.columns.get_mut(*self.key_column_map.get(&key)?)
+	fn handle_key_event(&mut self, key_event: KeyEvent) {
+		// key repeats aren't new presses
+		let new_press = match key_event.pressed {
+			true => self.held_keys.insert(key_event.key),
+			false => {
+				self.held_keys.remove(&key_event.key);
+				false
+			}
+		};
+
+		self.active_layer = self.find_active_layer();
+
+		if let Some(chart) = &mut self.chart {
+			if new_press {
+				chart.control_key_pressed(key_event.key, key_event.time);
+			}
+		}
+
+		for widget in &mut self.widgets {
+			widget.key_event(&key_event);
+		}
+
+		let Some(column_indices) = self.key_column_map.get(&key_event.key) else {
+			return;
+		};
+
+		// lane of the first active column pressed, if any
+		let mut pressed_lane = None;
+
+		for &i in column_indices {
+			let column = &mut self.columns[i];
+
+			// presses only go to columns that are currently active,
+			// but releases go everywhere so that no column stays stuck
+			let when_met = match &column.props.when {
+				Some(when) => when.is_met(&self.held_keys),
+				None => true,
+			};
+			let active = column.layer == self.active_layer && when_met;
+
+			if active || !key_event.pressed {
+				let was_pressed = column.pressed;
+				column.set_key_pressed(key_event.clone());
+
+				if let Some(midi_recorder) = &mut self.midi_recorder {
+					if column.layer == 0 && column.pressed != was_pressed {
+						midi_recorder.column_changed(i, column.pressed, key_event.time);
+					}
+				}
+			}
+
+			if active && new_press {
+				pressed_lane.get_or_insert(i);
+
+				// the base columns come first, their index being their lane in the chart
+				if let Some(chart) = &mut self.chart {
+					if column.layer == 0 {
+						chart.press(i, key_event.time);
+					}
+				}
+			}
+		}
+
+		if let Some(lane) = pressed_lane {
+			if let Some(stream) = &mut self.stream {
+				stream.press(lane, key_event.time);
+			}
+
+			if let Some(combo) = &mut self.combo {
+				combo.press(key_event.time);
+			}
+		}
 	}
 }
 
 impl Scene for KeyOverlayScene {
 	fn update(&mut self) {
-		while let Ok(key_event) = self.keyboard_rx.try_recv() {
-			let column = self.column_mut(key_event.key).unwrap();
-			column.set_key_pressed(key_event);
+		while let Ok(input_event) = self.input_rx.try_recv() {
+			match input_event {
+				InputEvent::Key(key_event) => self.handle_key_event(key_event),
+				InputEvent::MouseMove { pos, time } => {
+					for widget in &mut self.widgets {
+						widget.mouse_move(pos, time);
+					}
+				}
+				InputEvent::MouseButton { button, pressed, time } => {
+					for widget in &mut self.widgets {
+						widget.mouse_button(button, pressed, time);
+					}
+				}
+			}
 		}
 
 		if self.debug_mode {
@@ -218,13 +895,13 @@ impl Scene for KeyOverlayScene {
 			}
 			self.frame_deltas.push_back(self.now.elapsed().unwrap());
 
-			if self.frame_count % 100 == 0 {
+			if self.frame_count.checked_rem(100) == Some(0) {
 				let avg_delta = self.frame_deltas.iter().sum::<Duration>() / self.frame_deltas.len().max(1) as u32;
 				self.debug_texts = vec![
 					format!(
 						"SystemTime count: {}",
 						(self.columns.iter())
-							.map(|c| c.times.len().to_string())
+							.map(|c| c.trail.len().to_string())
 							.collect::<Vec<_>>()
 							.join(" | ")
 					),
@@ -234,9 +911,21 @@ impl Scene for KeyOverlayScene {
 		}
 		self.now = SystemTime::now();
 
+		if let Some(combo) = &mut self.combo {
+			combo.update(self.now);
+		}
+
+		if let Some(chart) = &mut self.chart {
+			chart.update(self.now);
+		}
+
 		self.frame_count += 1;
 	}
 
+	fn resize(&mut self, viewport: Vec2) {
+		self.layout = self.compute_layout(viewport);
+	}
+
 	fn inapp_key_event(&mut self, event: winit::event::KeyEvent, modifiers: ModifiersState) {
 		if modifiers.control_key()
 			&& event.state == ElementState::Released
@@ -247,337 +936,363 @@ impl Scene for KeyOverlayScene {
 		}
 	}
 
-	fn draw(&self, viewport: Vec2, drawer: &mut impl Drawer) {
-		let mut drawn_rects = 0;
-		let mut drawn_texts = 0;
+	fn exit(&mut self) {
+		if let Some(midi_recorder) = &self.midi_recorder {
+			if let Err(e) = midi_recorder.save() {
+				eprintln!("ERROR (MIDI export): {}", e);
+			}
+		}
+	}
 
+	fn draw(&self, viewport: Vec2, drawer: &mut impl Drawer, image_drawer: &mut impl ImageDrawer) {
 		drawer.clear();
 		drawer.begin_frame();
-		{
-			let key_size = vec2(self.default_key_width, self.key_height);
-			let spacing = self.key_spacing;
-			let key_y = match self.direction {
-				ScrollDirection::Up => viewport.y - 30.,
-				ScrollDirection::Down => 30.,
-			};
+		self.draw_overlay(viewport, drawer, image_drawer);
+		drawer.end_frame();
+	}
+}
+
+impl KeyOverlayScene {
+	/// Draws the columns and widgets, within a frame that has already begun.
+	pub fn draw_overlay(&self, viewport: Vec2, drawer: &mut impl Drawer, image_drawer: &mut impl ImageDrawer) {
+		let mut drawn_rects = 0;
+		let mut drawn_texts = 0;
 
-			let n_columns = self.columns.len() as f32;
+		let text_scale = self.layout.scale.y;
+		let key_border_width = KEY_BORDER_WIDTH * self.layout.scale.min_element();
+		let layer_columns =
+			(self.columns.iter().zip(&self.layout.columns)).filter(|(column, _)| column.layer == self.active_layer);
 
-			for (i, column) in self.columns.iter().enumerate() {
-				let color = match column.pressed {
-					true => column.props.hover_color,
-					false => 0x111111,
-				};
+		for (column, column_layout) in layer_columns {
+			let key_rect = &column_layout.key_rect;
+			let direction = column_layout.direction;
 
-				let i = i as f32 + 0.5;
-				let x_offset = (i - n_columns / 2.) * (key_size.x + spacing / 2.);
+			let color = match column.pressed {
+				true => column.props.hover_color,
+				false => 0x111111,
+			};
 
-				let key_rect = OwoRect {
-					pos: vec2(viewport.x / 2. + x_offset, key_y),
-					size: key_size,
-					origin: match self.direction {
-						ScrollDirection::Up => Anchor::BC,
-						ScrollDirection::Down => Anchor::TC,
-					},
+			let key_size = key_rect.size;
+
+			// key rectangle
+			drawer.draw_rect(&RectBlueprint {
+				rect: key_rect.to_rect(),
+				color,
+				border_color: column.props.border_color,
+				border_width: key_border_width,
+				corner_radius: 2.,
+				borders: [true, true, true, true],
+				alpha: 1.,
+			});
+			drawn_rects += 1;
+
+			// key and counter texts
+			{
+				let big_font_size = BIG_FONT_SIZE * text_scale;
+				let smol_font_size = SMOL_FONT_SIZE * text_scale;
+				let bottom_key_text_gap = BOTTOM_KEY_TEXT_GAP * text_scale;
+				let center_text_gap = CENTER_TEXT_GAP * text_scale;
+
+				let mut key_text = TextBlueprint {
+					text: &column.name,
+					x: key_rect.pos.x,
+					y: key_rect.pos.y,
+					font: &self.default_font,
+					size: 20.,
+					col: 0xeeeeee,
+					alpha: 1.,
 				};
 
-				const KEY_BORDER_WIDTH: f32 = 8.;
-
-				// key rectangle
-				drawer.draw_rect(&RectBlueprint {
-					rect: key_rect.to_rect(),
-					color,
-					border_color: column.props.border_color,
-					border_width: KEY_BORDER_WIDTH,
-					corner_radius: 2.,
-					borders: [true, true, true, true],
+				let mut counter_text = TextBlueprint {
+					text: &column.counter_text(),
+					x: key_rect.pos.x,
+					y: key_rect.pos.y,
+					font: &self.default_font,
+					size: 25.,
+					col: 0xeeeeee,
 					alpha: 1.,
-				});
-				drawn_rects += 1;
+				};
 
-				// key and counter texts
-				{
-					const BIG_FONT_SIZE: f32 = 25.;
-					const SMOL_FONT_SIZE: f32 = 20.;
-					const BOTTOM_KEY_TEXT_GAP: f32 = 5.;
-					const CENTER_TEXT_GAP: f32 = 2.;
-
-					let mut key_text = TextBlueprint {
-						text: &column.name,
-						x: key_rect.pos.x,
-						y: key_rect.pos.y,
-						font: &self.default_font,
-						size: 20.,
-						col: 0xeeeeee,
-						alpha: 1.,
-					};
+				let kt_rect;
+				let ct_rect;
 
-					let mut counter_text = TextBlueprint {
-						text: &format!("{}", column.count),
-						x: key_rect.pos.x,
-						y: key_rect.pos.y,
-						font: &self.default_font,
-						size: 25.,
-						col: 0xeeeeee,
-						alpha: 1.,
-					};
+				match (self.key_placement, self.counter_placement) {
+					(BoxPlacement::Inside, BoxPlacement::Inside) => {
+						// key and counter inside
+						// have key above and counter below with a gap
 
-					let kt_rect;
-					let ct_rect;
+						key_text.size = big_font_size;
+						counter_text.size = smol_font_size;
 
-					match (self.key_placement, self.counter_placement) {
-						(BoxPlacement::Inside, BoxPlacement::Inside) => {
-							// key and counter inside
-							// have key above and counter below with a gap
+						kt_rect = OwoRect {
+							pos: key_rect.center() - vec2(0., center_text_gap),
+							size: vec2(key_text.text_width(), key_text.text_height()),
+							origin: Anchor::BC,
+						};
 
-							key_text.size = BIG_FONT_SIZE;
-							counter_text.size = SMOL_FONT_SIZE;
+						ct_rect = OwoRect {
+							pos: key_rect.center() + vec2(0., center_text_gap),
+							size: vec2(counter_text.text_width(), counter_text.text_height()),
+							origin: Anchor::TC,
+						};
+					}
+					(BoxPlacement::Inside, BoxPlacement::Outside) => {
+						// key inside, counter outside
 
-							kt_rect = OwoRect {
-								pos: key_rect.center() - vec2(0., CENTER_TEXT_GAP),
-								size: vec2(key_text.text_width(), key_text.text_height()),
-								origin: Anchor::BC,
-							};
+						key_text.size = big_font_size;
+						counter_text.size = smol_font_size;
 
-							ct_rect = OwoRect {
-								pos: key_rect.center() + vec2(0., CENTER_TEXT_GAP),
+						kt_rect = OwoRect {
+							pos: key_rect.center(),
+							size: vec2(key_text.text_width(), key_text.text_height()),
+							origin: Anchor::CC,
+						};
+
+						ct_rect = match direction {
+							ScrollDirection::Up => OwoRect {
+								pos: key_rect.anchor(Anchor::BC) + vec2(0., bottom_key_text_gap),
 								size: vec2(counter_text.text_width(), counter_text.text_height()),
 								origin: Anchor::TC,
-							};
-						}
-						(BoxPlacement::Inside, BoxPlacement::Outside) => {
-							// key inside, counter outside
+							},
+							ScrollDirection::Down => OwoRect {
+								pos: key_rect.anchor(Anchor::TC) - vec2(0., bottom_key_text_gap),
+								size: vec2(counter_text.text_width(), counter_text.text_height()),
+								origin: Anchor::BC,
+							},
+						};
+					}
+					(BoxPlacement::Outside, BoxPlacement::Inside) => {
+						// key outside, counter inside
 
-							key_text.size = BIG_FONT_SIZE;
-							counter_text.size = SMOL_FONT_SIZE;
+						key_text.size = smol_font_size;
+						counter_text.size = big_font_size;
 
-							kt_rect = OwoRect {
-								pos: key_rect.center(),
+						kt_rect = match direction {
+							ScrollDirection::Up => OwoRect {
+								pos: key_rect.anchor(Anchor::BC) + vec2(0., bottom_key_text_gap),
 								size: vec2(key_text.text_width(), key_text.text_height()),
-								origin: Anchor::CC,
-							};
+								origin: Anchor::TC,
+							},
+							ScrollDirection::Down => OwoRect {
+								pos: key_rect.anchor(Anchor::TC) - vec2(0., bottom_key_text_gap),
+								size: vec2(key_text.text_width(), key_text.text_height()),
+								origin: Anchor::BC,
+							},
+						};
+
+						ct_rect = OwoRect {
+							pos: key_rect.center(),
+							size: vec2(counter_text.text_width(), counter_text.text_height()),
+							origin: Anchor::CC,
+						};
+					}
+					(BoxPlacement::Outside, BoxPlacement::Outside) => {
+						// key and counter outside
+						// have key on the left and counter on the right
 
-							ct_rect = match self.direction {
-								ScrollDirection::Up => OwoRect {
-									pos: key_rect.anchor(Anchor::BC) + vec2(0., BOTTOM_KEY_TEXT_GAP),
-									size: vec2(counter_text.text_width(), counter_text.text_height()),
-									origin: Anchor::TC,
-								},
-								ScrollDirection::Down => OwoRect {
-									pos: key_rect.anchor(Anchor::TC) - vec2(0., BOTTOM_KEY_TEXT_GAP),
-									size: vec2(counter_text.text_width(), counter_text.text_height()),
-									origin: Anchor::BC,
-								},
-							};
-						}
-						(BoxPlacement::Outside, BoxPlacement::Inside) => {
-							// key outside, counter inside
-
-							key_text.size = SMOL_FONT_SIZE;
-							counter_text.size = BIG_FONT_SIZE;
-
-							kt_rect = match self.direction {
-								ScrollDirection::Up => OwoRect {
-									pos: key_rect.anchor(Anchor::BC) + vec2(0., BOTTOM_KEY_TEXT_GAP),
-									size: vec2(key_text.text_width(), key_text.text_height()),
-									origin: Anchor::TC,
-								},
-								ScrollDirection::Down => OwoRect {
-									pos: key_rect.anchor(Anchor::TC) - vec2(0., BOTTOM_KEY_TEXT_GAP),
-									size: vec2(key_text.text_width(), key_text.text_height()),
-									origin: Anchor::BC,
-								},
-							};
+						key_text.size = smol_font_size;
+						counter_text.size = smol_font_size;
+
+						kt_rect = match direction {
+							ScrollDirection::Up => OwoRect {
+								pos: key_rect.anchor(Anchor::BL) + vec2(key_border_width, bottom_key_text_gap),
+								size: vec2(key_text.text_width(), key_text.text_height()),
+								origin: Anchor::TL,
+							},
+							ScrollDirection::Down => OwoRect {
+								pos: key_rect.anchor(Anchor::TL) + vec2(key_border_width, -bottom_key_text_gap),
+								size: vec2(key_text.text_width(), key_text.text_height()),
+								origin: Anchor::BL,
+							},
+						};
 
-							ct_rect = OwoRect {
-								pos: key_rect.center(),
+						ct_rect = match direction {
+							ScrollDirection::Up => OwoRect {
+								pos: key_rect.anchor(Anchor::BR) + vec2(-key_border_width, bottom_key_text_gap),
 								size: vec2(counter_text.text_width(), counter_text.text_height()),
-								origin: Anchor::CC,
-							};
-						}
-						(BoxPlacement::Outside, BoxPlacement::Outside) => {
-							// key and counter outside
-							// have key on the left and counter on the right
-
-							key_text.size = SMOL_FONT_SIZE;
-							counter_text.size = SMOL_FONT_SIZE;
-
-							kt_rect = match self.direction {
-								ScrollDirection::Up => OwoRect {
-									pos: key_rect.anchor(Anchor::BL) + vec2(KEY_BORDER_WIDTH, BOTTOM_KEY_TEXT_GAP),
-									size: vec2(key_text.text_width(), key_text.text_height()),
-									origin: Anchor::TL,
-								},
-								ScrollDirection::Down => OwoRect {
-									pos: key_rect.anchor(Anchor::TL) + vec2(KEY_BORDER_WIDTH, -BOTTOM_KEY_TEXT_GAP),
-									size: vec2(key_text.text_width(), key_text.text_height()),
-									origin: Anchor::BL,
-								},
-							};
+								origin: Anchor::TR,
+							},
+							ScrollDirection::Down => OwoRect {
+								pos: key_rect.anchor(Anchor::TR) + vec2(-key_border_width, -bottom_key_text_gap),
+								size: vec2(counter_text.text_width(), counter_text.text_height()),
+								origin: Anchor::BR,
+							},
+						};
+					}
+				}
 
-							ct_rect = match self.direction {
-								ScrollDirection::Up => OwoRect {
-									pos: key_rect.anchor(Anchor::BR) + vec2(-KEY_BORDER_WIDTH, BOTTOM_KEY_TEXT_GAP),
-									size: vec2(counter_text.text_width(), counter_text.text_height()),
-									origin: Anchor::TR,
-								},
-								ScrollDirection::Down => OwoRect {
-									pos: key_rect.anchor(Anchor::TR) + vec2(-KEY_BORDER_WIDTH, -BOTTOM_KEY_TEXT_GAP),
-									size: vec2(counter_text.text_width(), counter_text.text_height()),
-									origin: Anchor::BR,
-								},
+				let key_text_pos = kt_rect.top_left();
+				key_text.x = key_text_pos.x;
+				key_text.y = key_text_pos.y;
+
+				let counter_text_pos = ct_rect.top_left();
+				counter_text.x = counter_text_pos.x;
+				counter_text.y = counter_text_pos.y;
+
+				// // debug rectangles
+				// {
+				// 	drawer.draw_rect(&RectBlueprint {
+				// 		rect: kt_rect.to_rect(),
+				// 		color,
+				// 		border_color: 0xffff00,
+				// 		border_width: 1.,
+				// 		corner_radius: 0.,
+				// 		borders: [true, true, true, true],
+				// 		alpha: 1.,
+				// 	});
+
+				// 	drawer.draw_rect(&RectBlueprint {
+				// 		rect: ct_rect.to_rect(),
+				// 		color,
+				// 		border_color: 0xff00ff,
+				// 		border_width: 1.,
+				// 		corner_radius: 0.,
+				// 		borders: [true, true, true, true],
+				// 		alpha: 1.,
+				// 	});
+				// }
+
+				if self.display_keys {
+					match column.current_icon() {
+						Some((icon, tint)) => {
+							// the icon takes the place of the key text, fitted in the box it would've had
+							let inner_size = key_size - 2. * key_border_width;
+							let max_size = match (self.key_placement, self.counter_placement) {
+								(BoxPlacement::Inside, BoxPlacement::Inside) => {
+									vec2(inner_size.x, inner_size.y / 2. - center_text_gap) * 0.9
+								}
+								(BoxPlacement::Inside, BoxPlacement::Outside) => inner_size * 0.75,
+								(BoxPlacement::Outside, _) => vec2(inner_size.x, smol_font_size),
 							};
-						}
-					}
 
-					let key_text_pos = kt_rect.top_left();
-					key_text.x = key_text_pos.x;
-					key_text.y = key_text_pos.y;
-
-					let counter_text_pos = ct_rect.top_left();
-					counter_text.x = counter_text_pos.x;
-					counter_text.y = counter_text_pos.y;
-
-					// // debug rectangles
-					// {
-					// 	drawer.draw_rect(&RectBlueprint {
-					// 		rect: kt_rect.to_rect(),
-					// 		color,
-					// 		border_color: 0xffff00,
-					// 		border_width: 1.,
-					// 		corner_radius: 0.,
-					// 		borders: [true, true, true, true],
-					// 		alpha: 1.,
-					// 	});
-
-					// 	drawer.draw_rect(&RectBlueprint {
-					// 		rect: ct_rect.to_rect(),
-					// 		color,
-					// 		border_color: 0xff00ff,
-					// 		border_width: 1.,
-					// 		corner_radius: 0.,
-					// 		borders: [true, true, true, true],
-					// 		alpha: 1.,
-					// 	});
-					// }
-
-					if self.display_keys {
-						drawer.draw_text(&key_text);
-						drawn_texts += 1;
-					}
+							let icon_rect = OwoRect {
+								pos: kt_rect.pos,
+								size: icon.size() * (max_size / icon.size()).min_element(),
+								origin: kt_rect.origin,
+							};
 
-					if self.display_counters {
-						drawer.draw_text(&counter_text);
-						drawn_texts += 1;
+							image_drawer.draw_image(&ImageBlueprint {
+								image: icon,
+								rect: icon_rect.to_rect(),
+								tint,
+								alpha: 1.,
+							});
+						}
+						None => {
+							drawer.draw_text(&key_text);
+							drawn_texts += 1;
+						}
 					}
 				}
 
-				// history rectangles
-				let mut opt_prev_time: Option<SystemTime> = column.pressed.then_some(self.now);
-
-				for time in column.times.iter().copied() {
-					match opt_prev_time {
-						Some(prev_time) => {
-							let rect = {
-								let base_pos = key_rect.anchor(match self.direction {
-									ScrollDirection::Up => Anchor::TL,
-									ScrollDirection::Down => Anchor::BL,
-								});
+				if self.display_counters {
+					drawer.draw_text(&counter_text);
+					drawn_texts += 1;
+				}
+			}
 
-								let this_time = self.duration_since_now(time);
-								let prev_time = self.duration_since_now(prev_time);
-								let now_time = self.duration_since_now(self.now);
+			// history rectangles
+			let mut trail_labels = Vec::new();
+
+			match column.props.key_trails {
+				KeyTrails::Merged => {
+					let lane = Lane {
+						column,
+						layout: column_layout,
+						trail: &column.trail,
+						pressed: column.pressed,
+						x: key_rect.top_left().x,
+						width: key_size.x,
+						color: column.props.color,
+					};
 
-								let y_start = (this_time - now_time).as_secs_f32() * self.speed;
-								let y_end = (prev_time - now_time).as_secs_f32() * self.speed;
+					drawn_rects += self.draw_trail(drawer, &lane, &mut trail_labels);
+				}
+				KeyTrails::Split | KeyTrails::Colored => {
+					let n_keys = column.props.keys.len().max(1) as f32;
+
+					for (index, &key) in column.props.keys.iter().enumerate() {
+						let (x, width) = match column.props.key_trails {
+							KeyTrails::Split => {
+								let width = key_size.x / n_keys;
+								(key_rect.top_left().x + index as f32 * width, width)
+							}
+							_ => (key_rect.top_left().x, key_size.x),
+						};
+
+						let lane = Lane {
+							column,
+							layout: column_layout,
+							trail: &column.key_trails[&key],
+							pressed: column.key_active(key),
+							x,
+							width,
+							color: column.key_color(key),
+						};
+
+						drawn_rects += self.draw_trail(drawer, &lane, &mut trail_labels);
+					}
+				}
+			}
 
-								// stop drawing rectangles once off-screen
-								if y_end >= viewport.y {
-									break;
-								}
+			drawn_texts += self.draw_trail_labels(drawer, column_layout, &mut trail_labels);
+		}
 
-								// clamp coordinates to avoid floating point glitches
-								let y_start = y_start.clamp(0.0, viewport.y);
-								let y_end = y_end.clamp(0.0, viewport.y);
+		drawn_rects += self.draw_chart_notes(drawer);
 
-								let (y, h) = match self.direction {
-									ScrollDirection::Up => (-y_start, y_start - y_end),
-									ScrollDirection::Down => (y_start, y_end - y_start),
-								};
+		for widget in &self.widgets {
+			let (rects, texts) = widget.draw(drawer, &self.default_font, self.layout.scale, self.now);
+			drawn_rects += rects;
+			drawn_texts += texts;
+		}
 
-								Rect {
-									x: base_pos.x,
-									y: base_pos.y + y,
-									w: key_size.x,
-									h,
-								}
-							};
+		drawn_texts += self.draw_hud(viewport, drawer);
 
-							drawer.draw_rect(&RectBlueprint {
-								rect,
-								color: column.props.color,
-								border_color: 0x000000,
-								border_width: 0.,
-								corner_radius: 0.,
-								borders: [false, false, false, false],
-								alpha: column.props.alpha,
-							});
-							drawn_rects += 1;
+		if self.debug_mode {
+			drawn_texts += 2;
 
-							opt_prev_time = None;
-						}
-						None => opt_prev_time = Some(time),
-					}
-				}
-			}
+			let line_spacing = 15.0;
+			let total_text_height = line_spacing * (self.debug_texts.len() as f32 + 1.0);
+			let start_y = match self.direction {
+				ScrollDirection::Up => 5.0,
+				ScrollDirection::Down => viewport.y - 5.0 - total_text_height,
+			};
 
-			if self.debug_mode {
-				drawn_texts += 2;
+			drawer.draw_rect(&RectBlueprint {
+				rect: Rect::new(0.0, start_y - 5.0, viewport.x, total_text_height + 10.0),
+				color: 0x000000,
+				border_color: 0x000000,
+				border_width: 0.0,
+				corner_radius: 0.0,
+				borders: [false, false, false, false],
+				alpha: 1.0,
+			});
 
-				let line_spacing = 15.0;
-				let total_text_height = line_spacing * (self.debug_texts.len() as f32 + 1.0);
-				let start_y = match self.direction {
-					ScrollDirection::Up => 5.0,
-					ScrollDirection::Down => viewport.y - 5.0 - total_text_height,
-				};
+			let debug_text = format!("Drawn | Rectangles = {} | Texts = {}", drawn_rects, drawn_texts);
+			drawer.draw_text(&TextBlueprint {
+				text: &debug_text,
+				x: 5.0,
+				y: start_y,
+				font: &self.default_font,
+				size: 15.,
+				col: 0x64ff64,
+				alpha: 1.,
+			});
 
-				drawer.draw_rect(&RectBlueprint {
-					rect: Rect::new(0.0, start_y - 5.0, viewport.x, total_text_height + 10.0),
-					color: 0x000000,
-					border_color: 0x000000,
-					border_width: 0.0,
-					corner_radius: 0.0,
-					borders: [false, false, false, false],
-					alpha: 1.0,
-				});
+			let debug_text_start_y = start_y + line_spacing;
 
-				let debug_text = format!("Drawn | Rectangles = {} | Texts = {}", drawn_rects, drawn_texts);
+			for (i, debug_text) in self.debug_texts.iter().enumerate() {
 				drawer.draw_text(&TextBlueprint {
-					text: &debug_text,
+					text: debug_text,
 					x: 5.0,
-					y: start_y,
+					y: debug_text_start_y + i as f32 * line_spacing,
 					font: &self.default_font,
 					size: 15.,
 					col: 0x64ff64,
 					alpha: 1.,
 				});
-
-				let debug_text_start_y = start_y + line_spacing;
-
-				for (i, debug_text) in self.debug_texts.iter().enumerate() {
-					drawer.draw_text(&TextBlueprint {
-						text: debug_text,
-						x: 5.0,
-						y: debug_text_start_y + i as f32 * line_spacing,
-						font: &self.default_font,
-						size: 15.,
-						col: 0x64ff64,
-						alpha: 1.,
-					});
-				}
 			}
 		}
-		drawer.end_frame();
 	}
 }
 
@@ -589,12 +1304,49 @@ struct Cli {
 	config: Option<PathBuf>,
 	#[arg(short, long, help = "Name of a config stored in the config directory")]
 	preset: Option<PathBuf>,
+	#[arg(
+		short,
+		long,
+		help = "Size the window to fit the layout, ignoring the configured size"
+	)]
+	auto_size: bool,
+	#[arg(short, long, value_enum, default_value_t, help = "What to show")]
+	mode: Mode,
+	#[arg(long, help = "Chart to practice timing on, as a .osu, .csv or .toml file")]
+	chart: Option<PathBuf>,
+	#[arg(
+		long,
+		help = "osu! replay (.osr) or MIDI file to play instead of listening to the keyboard"
+	)]
+	replay: Option<PathBuf>,
+	#[arg(long, help = "MIDI file to record the session into, written when closing the window")]
+	export_midi: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+enum Mode {
+	/// Key columns with scrolling trails
+	#[default]
+	Keys,
+	/// Typed shortcuts as text bubbles, for screencasts
+	Keystrokes,
+	/// Typing speed and backspace stats
+	Typing,
+	/// Tapping as fast as possible for some time or some taps
+	TapTest,
+	/// Pressing a key as soon as a cue shows up
+	Reaction,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
 	let Cli {
 		config: config_path,
 		preset,
+		auto_size,
+		mode,
+		chart,
+		replay,
+		export_midi,
 	} = Cli::parse();
 
 	let config_dir = dirs::config_dir()
@@ -610,7 +1362,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 		(None, None) => (config_dir.join("cowonfig.toml"), true),
 	};
 
-	let config = match fs::read_to_string(&config_path) {
+	let mut config: Config = match fs::read_to_string(&config_path) {
 		Ok(c) => toml::from_str(&c)?,
 		Err(e) if e.kind() == io::ErrorKind::NotFound => {
 			if !do_default {
@@ -624,36 +1376,220 @@ fn main() -> Result<(), Box<dyn Error>> {
 		Err(e) => return Err(e.into()),
 	};
 
+	config.reaction.check_delays()?;
+
+	// icon paths are relative to the config they're defined in
+	let config_parent = config_path.parent().unwrap_or(&config_dir).to_path_buf();
+	let layer_columns = config.layers.iter_mut().flat_map(|layer| &mut layer.columns);
+	for column in config.columns.iter_mut().chain(layer_columns) {
+		column.icon = column.icon.take().map(|icon| config_parent.join(icon));
+		column.pressed_icon = column.pressed_icon.take().map(|icon| config_parent.join(icon));
+	}
+	config.typing.results_file = config.typing.results_file.take().map(|file| config_parent.join(file));
+
+	if auto_size {
+		config.window.width = Length::AUTO;
+		config.window.height = Length::AUTO;
+	}
+
+	let auto_window_size = layout::auto_window_size(&config);
+	let window_size = vec2(
+		config.window.width.resolve(auto_window_size.x),
+		config.window.height.resolve(auto_window_size.y),
+	);
+
+	match mode {
+		Mode::Keys => {
+			let (key_columns, widgets, mut filter) = load_overlay(&config)?;
+
+			let chart = match chart {
+				Some(chart) => {
+					let chart = Chart::load(&chart, config.columns.len())?;
+					Some(ChartPractice::new(chart, config.chart.clone(), config.columns.len()))
+				}
+				None => None,
+			};
+
+			if let (Some(chart), Some(keys)) = (&chart, &mut filter.keys) {
+				keys.extend(chart.control_keys());
+			}
+
+			let input_rx = match replay {
+				Some(replay) => {
+					let events = match replay.extension().and_then(|ext| ext.to_str()) {
+						Some("mid" | "midi") => midi::load(&replay, &config.midi, &config.columns)?,
+						_ => osr::load(&replay, &config.replay.lane_keys(&config.columns))?,
+					};
+
+					spawn_playback(events)?
+				}
+				None => spawn_listener(filter)?,
+			};
+
+			let mut scene = KeyOverlayScene::new(input_rx, &config, window_size, key_columns, widgets);
+			scene.chart = chart;
+			scene.midi_recorder = export_midi.map(|path| MidiRecorder::new(path, &config.midi, config.columns.len()));
+			run_scene(&config, window_size, scene)
+		}
+		Mode::Keystrokes => {
+			let input_rx = spawn_listener(ListenerFilter::all_keys())?;
+			let scene = KeystrokeScene::new(input_rx, config.keystrokes.clone());
+			run_scene(&config, window_size, scene)
+		}
+		Mode::Typing => {
+			let input_rx = spawn_listener(ListenerFilter::all_keys())?;
+			let scene = TypingScene::new(input_rx, config.typing.clone());
+			run_scene(&config, window_size, scene)
+		}
+		Mode::TapTest => {
+			let (key_columns, widgets, mut filter) = load_overlay(&config)?;
+			if let Some(keys) = &mut filter.keys {
+				keys.insert(config.tap_test.start_key);
+			}
+
+			let input_rx = spawn_listener(filter)?;
+
+			// the overlay gets the events once the test is done with them
+			let (overlay_tx, overlay_rx) = mpsc::channel();
+			let overlay = KeyOverlayScene::new(overlay_rx, &config, window_size, key_columns, widgets);
+
+			// taps on the columns of every layer count
+			let tap_keys = (config.column_sets().flatten())
+				.flat_map(|column| &column.keys)
+				.copied()
+				.collect();
+			let records = Records::load(&config_dir.join("tap-test-bests.toml"))?;
+			let scene = TapTestScene::new(
+				input_rx,
+				overlay_tx,
+				overlay,
+				config.tap_test.clone(),
+				tap_keys,
+				records,
+			);
+			run_scene(&config, window_size, scene)
+		}
+		Mode::Reaction => {
+			let input_rx = spawn_listener(ListenerFilter {
+				keys: Some(config.reaction.keys.iter().copied().collect()),
+				mouse_moves: false,
+				mouse_buttons: false,
+			})?;
+			let scene = ReactionScene::new(input_rx, config.reaction.clone());
+			run_scene(&config, window_size, scene)
+		}
+	}
+}
+
+/// Key columns and widgets of the overlay, with what the listener needs to send them.
+type Overlay = (Vec<KeyColumn>, Vec<Widget>, ListenerFilter);
+
+fn load_overlay(config: &Config) -> Result<Overlay, Box<dyn Error>> {
 	let mut keys = HashSet::new();
 
-	let key_columns = (config.columns.iter().cloned())
-		.inspect(|column| keys.extend(column.keys.iter().copied()))
-		.map(KeyColumn::new)
-		.collect::<Vec<_>>();
+	for layer in &config.layers {
+		keys.extend(layer.hold.iter().copied());
+	}
+
+	let key_columns = (config.column_sets().enumerate())
+		.flat_map(|(layer, columns)| columns.iter().map(move |column| (column.clone(), layer)))
+		.inspect(|(column, _)| {
+			keys.extend(column.keys.iter().copied());
+
+			if let Some(when) = &column.when {
+				keys.extend(when.held.iter().chain(&when.not_held).copied());
+			}
+		})
+		.map(|(column, layer)| KeyColumn::new(column, layer))
+		.collect::<Result<Vec<_>, _>>()?;
+
+	let widgets = (config.widgets.iter().cloned())
+		.map(Widget::new)
+		.inspect(|widget| {
+			if let Ok(widget) = widget {
+				keys.extend(widget.keys());
+			}
+		})
+		.collect::<Result<Vec<_>, _>>()?;
+
+	let filter = ListenerFilter {
+		keys: match widgets.iter().any(Widget::listens_to_all_keys) {
+			true => None,
+			false => Some(keys),
+		},
+		// mouse moves are way too frequent to send them for nothing
+		mouse_moves: widgets.iter().any(Widget::listens_to_mouse_moves),
+		mouse_buttons: widgets.iter().any(Widget::listens_to_mouse_buttons),
+	};
+
+	Ok((key_columns, widgets, filter))
+}
 
-	let (keyboard_tx, keyboard_rx) = mpsc::channel::<KeyEvent>();
+/// What the global listener sends to the scene.
+struct ListenerFilter {
+	/// Keys to send, all of them if `None`
+	keys: Option<HashSet<rdev::Key>>,
+	mouse_moves: bool,
+	mouse_buttons: bool,
+}
 
-	let scene = KeyOverlayScene::new(keyboard_rx, &config, key_columns);
+impl ListenerFilter {
+	fn all_keys() -> Self {
+		Self {
+			keys: None,
+			mouse_moves: false,
+			mouse_buttons: false,
+		}
+	}
+}
+
+/// Listens to global input events in the background.
+fn spawn_listener(filter: ListenerFilter) -> io::Result<mpsc::Receiver<InputEvent>> {
+	let (input_tx, input_rx) = mpsc::channel::<InputEvent>();
+	let ListenerFilter {
+		keys,
+		mouse_moves,
+		mouse_buttons,
+	} = filter;
+	let listens_to = move |key: &rdev::Key| match &keys {
+		Some(keys) => keys.contains(key),
+		None => true,
+	};
 
 	thread::Builder::new()
-		.name("Global Keyboard Listener".to_string())
+		.name("Global Input Listener".to_string())
 		.spawn(move || {
 			let result = rdev::listen(move |event| {
-				let (key, pressed) = match event.event_type {
-					rdev::EventType::KeyPress(k) => (k, true),
-					rdev::EventType::KeyRelease(k) => (k, false),
+				let input_event = match event.event_type {
+					rdev::EventType::KeyPress(key) | rdev::EventType::KeyRelease(key) if !listens_to(&key) => return,
+					rdev::EventType::KeyPress(key) => InputEvent::Key(KeyEvent {
+						key,
+						time: event.time,
+						pressed: true,
+					}),
+					rdev::EventType::KeyRelease(key) => InputEvent::Key(KeyEvent {
+						key,
+						time: event.time,
+						pressed: false,
+					}),
+					rdev::EventType::MouseMove { x, y } if mouse_moves => InputEvent::MouseMove {
+						pos: vec2(x as f32, y as f32),
+						time: event.time,
+					},
+					rdev::EventType::ButtonPress(button) if mouse_buttons => InputEvent::MouseButton {
+						button,
+						pressed: true,
+						time: event.time,
+					},
+					rdev::EventType::ButtonRelease(button) if mouse_buttons => InputEvent::MouseButton {
+						button,
+						pressed: false,
+						time: event.time,
+					},
 					_ => return,
 				};
 
-				if !keys.contains(&key) {
-					return;
-				}
-
-				let result = keyboard_tx.send(KeyEvent {
-					key,
-					time: event.time,
-					pressed,
-				});
+				let result = input_tx.send(input_event);
 
 				if let Err(e) = result {
 					eprintln!("ERROR (tx.send): {}", e);
@@ -665,15 +1601,30 @@ fn main() -> Result<(), Box<dyn Error>> {
 			}
 		})?;
 
-	let (width, height) = (config.window.width, config.window.height);
+	Ok(input_rx)
+}
+
+fn run_scene(config: &Config, window_size: Vec2, scene: impl Scene + 'static) -> Result<(), Box<dyn Error>> {
+	let width = window_size.x * config.scale;
+	let height = window_size.y * config.scale;
 
-	let app_frame = AppFrame::init(
-		WindowBuilder::new()
-			.with_title("OwOverlay")
-			.with_transparent(config.window.transparent)
-			.with_resizable(config.window.resizable)
-			.with_inner_size(PhysicalSize::new(width, height)),
-	)?;
+	let window_builder = WindowBuilder::new()
+		.with_title("OwOverlay")
+		.with_transparent(config.window.transparent)
+		.with_resizable(config.window.resizable);
+
+	let window_builder = match config.window.dpi_aware {
+		true => window_builder.with_inner_size(LogicalSize::new(width, height)),
+		false => window_builder.with_inner_size(PhysicalSize::new(width, height)),
+	};
+
+	let app_frame = AppFrame::init(window_builder)?;
+
+	app_frame.run(OwOverlayApp::new(config.scale, config.window.dpi_aware, scene))
+}
 
-	app_frame.run(OwOverlayApp::new(width, height, scene))
+/// Time `ms` milliseconds after the epoch, for tests that only care about the gaps between times.
+#[cfg(test)]
+fn at(ms: u64) -> SystemTime {
+	SystemTime::UNIX_EPOCH + Duration::from_millis(ms)
 }
@@ -0,0 +1,434 @@
+//! Standard MIDI Files, with the base columns mapped to notes
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::config::{ColumnProps, MidiProps};
+use crate::playback::TimedKeyEvent;
+
+/// Exported files use 480 ticks per beat at 120 BPM.
+const TICKS_PER_BEAT: u16 = 480;
+const TEMPO: u32 = 500_000;
+
+const NOTE_OFF: u8 = 0x80;
+const NOTE_ON: u8 = 0x90;
+const META: u8 = 0xff;
+const META_TEMPO: u8 = 0x51;
+const META_END_OF_TRACK: u8 = 0x2f;
+const SYSEX: u8 = 0xf0;
+const SYSEX_ESCAPE: u8 = 0xf7;
+
+/// Records the presses and releases of the base columns as notes.
+pub struct MidiRecorder {
+	path: PathBuf,
+	notes: Vec<u8>,
+	channel: u8,
+	velocity: u8,
+	start: SystemTime,
+	/// Time, note and whether it's a note on
+	events: Vec<(SystemTime, u8, bool)>,
+}
+
+impl MidiRecorder {
+	pub fn new(path: PathBuf, props: &MidiProps, n_columns: usize) -> Self {
+		Self {
+			path,
+			notes: props.column_notes(n_columns),
+			channel: props.channel & 0x0f,
+			velocity: props.velocity.min(127),
+			start: SystemTime::now(),
+			events: Vec::new(),
+		}
+	}
+
+	pub fn column_changed(&mut self, column: usize, pressed: bool, time: SystemTime) {
+		if let Some(&note) = self.notes.get(column) {
+			self.events.push((time, note, pressed));
+		}
+	}
+
+	/// Writes the recording as a single track file.
+	pub fn save(&self) -> Result<(), Box<dyn Error>> {
+		fs::write(&self.path, self.encode())?;
+		Ok(())
+	}
+
+	fn encode(&self) -> Vec<u8> {
+		let ticks_per_second = TICKS_PER_BEAT as f64 * 1_000_000. / TEMPO as f64;
+
+		let mut track = Vec::new();
+		write_vlq(&mut track, 0);
+		track.extend([META, META_TEMPO, 3]);
+		track.extend(&TEMPO.to_be_bytes()[1..]);
+
+		let mut events = self.events.clone();
+		events.sort_by_key(|&(time, _, _)| time);
+
+		let mut prev_tick = 0;
+		let mut held = Vec::new();
+		for (time, note, on) in events {
+			let seconds = time.duration_since(self.start).unwrap_or_default().as_secs_f64();
+			let tick = (seconds * ticks_per_second).round() as u32;
+
+			write_vlq(&mut track, tick - prev_tick);
+			prev_tick = tick;
+
+			match on {
+				true => {
+					held.push(note);
+					track.extend([NOTE_ON | self.channel, note, self.velocity]);
+				}
+				false => {
+					if let Some(i) = held.iter().position(|&held| held == note) {
+						held.remove(i);
+					}
+					track.extend([NOTE_OFF | self.channel, note, 0]);
+				}
+			}
+		}
+
+		// keys still down when the recording stops get released at its end
+		held.sort_unstable();
+		for note in held {
+			write_vlq(&mut track, 0);
+			track.extend([NOTE_OFF | self.channel, note, 0]);
+		}
+
+		write_vlq(&mut track, 0);
+		track.extend([META, META_END_OF_TRACK, 0]);
+
+		let mut file = Vec::new();
+		file.extend(b"MThd");
+		file.extend(6u32.to_be_bytes());
+		// format 0, with a single track
+		file.extend(0u16.to_be_bytes());
+		file.extend(1u16.to_be_bytes());
+		file.extend(TICKS_PER_BEAT.to_be_bytes());
+
+		file.extend(b"MTrk");
+		file.extend((track.len() as u32).to_be_bytes());
+		file.extend(track);
+		file
+	}
+}
+
+fn write_vlq(out: &mut Vec<u8>, mut value: u32) {
+	let mut bytes = vec![(value & 0x7f) as u8];
+	value >>= 7;
+
+	while value > 0 {
+		bytes.push((value & 0x7f) as u8 | 0x80);
+		value >>= 7;
+	}
+
+	out.extend(bytes.iter().rev());
+}
+
+/// Reads the big-endian values of MIDI files.
+struct Reader<'a> {
+	data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+	fn bytes(&mut self, n: usize) -> Result<&'a [u8], Box<dyn Error>> {
+		if self.data.len() < n {
+			return Err("unexpected end of the MIDI file".into());
+		}
+
+		let (bytes, rest) = self.data.split_at(n);
+		self.data = rest;
+		Ok(bytes)
+	}
+
+	fn byte(&mut self) -> Result<u8, Box<dyn Error>> {
+		Ok(self.bytes(1)?[0])
+	}
+
+	fn u16(&mut self) -> Result<u16, Box<dyn Error>> {
+		Ok(u16::from_be_bytes(self.bytes(2)?.try_into()?))
+	}
+
+	fn u32(&mut self) -> Result<u32, Box<dyn Error>> {
+		Ok(u32::from_be_bytes(self.bytes(4)?.try_into()?))
+	}
+
+	fn vlq(&mut self) -> Result<u32, Box<dyn Error>> {
+		let mut value = 0;
+
+		for _ in 0..4 {
+			let byte = self.byte()?;
+			value = (value << 7) | (byte & 0x7f) as u32;
+
+			if byte & 0x80 == 0 {
+				return Ok(value);
+			}
+		}
+
+		Err("invalid variable length quantity in the MIDI file".into())
+	}
+}
+
+enum TrackEvent {
+	Tempo(u32),
+	Note { note: u8, on: bool },
+}
+
+/// Loads the notes of every track and channel, each note mapped to a base column pressing its first key.
+pub fn load(path: &Path, props: &MidiProps, columns: &[ColumnProps]) -> Result<Vec<TimedKeyEvent>, Box<dyn Error>> {
+	let data = fs::read(path)?;
+
+	if !data.starts_with(b"MThd") {
+		return Err(format!("{} isn't a MIDI file", path.display()).into());
+	}
+
+	parse(&data, props, columns)
+}
+
+fn parse(data: &[u8], props: &MidiProps, columns: &[ColumnProps]) -> Result<Vec<TimedKeyEvent>, Box<dyn Error>> {
+	let mut reader = Reader { data };
+
+	// "MThd", checked by `load`
+	reader.bytes(4)?;
+
+	let header_len = reader.u32()? as usize;
+	let mut header = Reader {
+		data: reader.bytes(header_len)?,
+	};
+	let _format = header.u16()?;
+	let _n_tracks = header.u16()?;
+	let division = header.u16()?;
+
+	let mut track_events = Vec::new();
+
+	while !reader.data.is_empty() {
+		let id = reader.bytes(4)?;
+		let len = reader.u32()? as usize;
+		let chunk = reader.bytes(len)?;
+
+		// other chunks are meant to be skipped
+		if id == b"MTrk" {
+			read_track(chunk, &mut track_events)?;
+		}
+	}
+
+	// tempo changes apply to the notes at the same tick
+	track_events.sort_by_key(|(tick, event)| (*tick, matches!(event, TrackEvent::Note { .. })));
+
+	let notes = props.column_notes(columns.len());
+	let mut events = Vec::new();
+	let mut tempo = TEMPO;
+	let mut prev_tick = 0;
+	let mut seconds = 0.;
+
+	for (tick, event) in track_events {
+		let ticks = (tick - prev_tick) as f64;
+		prev_tick = tick;
+
+		// SMPTE divisions have a fixed duration per tick, in negative frames per second then ticks per frame
+		seconds += match division & 0x8000 {
+			0 => ticks * tempo as f64 / 1_000_000. / division.max(1) as f64,
+			_ => {
+				let fps = -((division >> 8) as i8) as f64;
+				ticks / (fps * (division & 0xff) as f64).max(1.)
+			}
+		};
+
+		match event {
+			TrackEvent::Tempo(new_tempo) => tempo = new_tempo,
+			TrackEvent::Note { note, on } => {
+				let Some(column) = notes.iter().position(|&n| n == note) else {
+					continue;
+				};
+
+				if let Some(&key) = columns.get(column).and_then(|column| column.keys.first()) {
+					events.push(TimedKeyEvent {
+						time: Duration::from_secs_f64(seconds),
+						key,
+						pressed: on,
+					});
+				}
+			}
+		}
+	}
+
+	Ok(events)
+}
+
+fn read_track(data: &[u8], events: &mut Vec<(u64, TrackEvent)>) -> Result<(), Box<dyn Error>> {
+	let mut reader = Reader { data };
+	let mut tick = 0;
+	let mut running_status = None;
+
+	while !reader.data.is_empty() {
+		tick += reader.vlq()? as u64;
+
+		let status = match reader.data.first() {
+			// running status, the byte being the first data byte
+			Some(&byte) if byte < 0x80 => running_status.ok_or("MIDI data without a status")?,
+			_ => reader.byte()?,
+		};
+
+		match status {
+			// running status doesn't carry over meta and SysEx events
+			META => {
+				running_status = None;
+
+				let kind = reader.byte()?;
+				let len = reader.vlq()? as usize;
+				let data = reader.bytes(len)?;
+
+				if kind == META_TEMPO && len == 3 {
+					events.push((
+						tick,
+						TrackEvent::Tempo(u32::from_be_bytes([0, data[0], data[1], data[2]])),
+					));
+				}
+			}
+			SYSEX | SYSEX_ESCAPE => {
+				running_status = None;
+
+				let len = reader.vlq()? as usize;
+				reader.bytes(len)?;
+			}
+			// the other system messages are only sent live, never stored in files
+			0xf1..=0xfe => return Err(format!("invalid status byte {:#04x} in the MIDI file", status).into()),
+			_ => {
+				running_status = Some(status);
+
+				let data = match status & 0xf0 {
+					// program change and channel pressure
+					0xc0 | 0xd0 => reader.bytes(1)?,
+					_ => reader.bytes(2)?,
+				};
+
+				match status & 0xf0 {
+					NOTE_ON => events.push((
+						tick,
+						TrackEvent::Note {
+							note: data[0],
+							// a note on with no velocity is a note off
+							on: data[1] > 0,
+						},
+					)),
+					NOTE_OFF => events.push((
+						tick,
+						TrackEvent::Note {
+							note: data[0],
+							on: false,
+						},
+					)),
+					_ => (),
+				}
+			}
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use rdev::Key;
+
+	fn columns() -> Vec<ColumnProps> {
+		[Key::KeyD, Key::KeyF, Key::KeyJ, Key::KeyK]
+			.into_iter()
+			.map(|key| ColumnProps::new(None, vec![key]))
+			.collect()
+	}
+
+	fn events(data: &[u8]) -> Vec<(u64, Key, bool)> {
+		(parse(data, &MidiProps::default(), &columns()).unwrap().into_iter())
+			.map(|event| {
+				(
+					(event.time.as_secs_f64() * 1000.).round() as u64,
+					event.key,
+					event.pressed,
+				)
+			})
+			.collect()
+	}
+
+	/// A format 0 file with a single track.
+	fn file(track: &[u8]) -> Vec<u8> {
+		let mut data = b"MThd".to_vec();
+		data.extend(6u32.to_be_bytes());
+		data.extend([0, 0, 0, 1]);
+		data.extend(TICKS_PER_BEAT.to_be_bytes());
+		data.extend(b"MTrk");
+		data.extend((track.len() as u32).to_be_bytes());
+		data.extend(track);
+		data
+	}
+
+	#[test]
+	fn exported_sessions_load_back() {
+		let mut recorder = MidiRecorder::new(PathBuf::new(), &MidiProps::default(), 4);
+		let at = |ms| recorder.start + Duration::from_millis(ms);
+		let times = [at(250), at(500), at(1000), at(1125)];
+
+		recorder.column_changed(0, true, times[0]);
+		recorder.column_changed(2, true, times[1]);
+		recorder.column_changed(0, false, times[2]);
+		recorder.column_changed(2, false, times[3]);
+
+		assert_eq!(
+			events(&recorder.encode()),
+			[
+				(250, Key::KeyD, true),
+				(500, Key::KeyJ, true),
+				(1000, Key::KeyD, false),
+				(1125, Key::KeyJ, false),
+			]
+		);
+	}
+
+	#[test]
+	fn held_notes_end_with_the_recording() {
+		let mut recorder = MidiRecorder::new(PathBuf::new(), &MidiProps::default(), 4);
+		let at = |ms| recorder.start + Duration::from_millis(ms);
+		let times = [at(250), at(500), at(750)];
+
+		recorder.column_changed(1, true, times[0]);
+		recorder.column_changed(3, true, times[1]);
+		recorder.column_changed(3, false, times[2]);
+
+		assert_eq!(
+			events(&recorder.encode()),
+			[
+				(250, Key::KeyF, true),
+				(500, Key::KeyK, true),
+				(750, Key::KeyK, false),
+				(750, Key::KeyF, false),
+			]
+		);
+	}
+
+	#[test]
+	fn running_status_stops_at_meta_events() {
+		let note = MidiProps::default().column_notes(1)[0];
+
+		let track = [0, NOTE_ON, note, 100, 0, note + 1, 100, 120, note, 0];
+		assert_eq!(
+			events(&file(&track)),
+			[(0, Key::KeyD, true), (0, Key::KeyF, true), (125, Key::KeyD, false)]
+		);
+
+		let track = [0, NOTE_ON, note, 100, 0, META, 0x01, 0, 0, note, 0];
+		assert!(parse(&file(&track), &MidiProps::default(), &columns()).is_err());
+
+		let track = [0, NOTE_ON, note, 100, 0, SYSEX, 1, SYSEX_ESCAPE, 0, note, 0];
+		assert!(parse(&file(&track), &MidiProps::default(), &columns()).is_err());
+	}
+
+	#[test]
+	fn live_system_messages_are_invalid() {
+		for status in [0xf1, 0xf8, 0xfe] {
+			assert!(parse(&file(&[0, status, 0]), &MidiProps::default(), &columns()).is_err());
+		}
+	}
+}
@@ -0,0 +1,295 @@
+//! osu! replays (.osr), turned into key events for the lanes
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::playback::TimedKeyEvent;
+
+const MODE_MANIA: u8 = 3;
+
+/// The last frame holds the RNG seed instead of inputs.
+const SEED_FRAME_TIME: i64 = -12345;
+
+const MOD_DOUBLE_TIME: u32 = 1 << 6;
+const MOD_HALF_TIME: u32 = 1 << 8;
+
+// key state of the other modes, K1 and K2 also setting M1 and M2
+const M1: u32 = 1 << 0;
+const M2: u32 = 1 << 1;
+const K1: u32 = 1 << 2;
+const K2: u32 = 1 << 3;
+
+/// Reads the little-endian values and strings of the .osr format.
+struct Reader<'a> {
+	data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+	fn bytes(&mut self, n: usize) -> Result<&'a [u8], Box<dyn Error>> {
+		if self.data.len() < n {
+			return Err("unexpected end of the replay".into());
+		}
+
+		let (bytes, rest) = self.data.split_at(n);
+		self.data = rest;
+		Ok(bytes)
+	}
+
+	fn byte(&mut self) -> Result<u8, Box<dyn Error>> {
+		Ok(self.bytes(1)?[0])
+	}
+
+	fn short(&mut self) -> Result<u16, Box<dyn Error>> {
+		Ok(u16::from_le_bytes(self.bytes(2)?.try_into()?))
+	}
+
+	fn int(&mut self) -> Result<u32, Box<dyn Error>> {
+		Ok(u32::from_le_bytes(self.bytes(4)?.try_into()?))
+	}
+
+	fn long(&mut self) -> Result<u64, Box<dyn Error>> {
+		Ok(u64::from_le_bytes(self.bytes(8)?.try_into()?))
+	}
+
+	fn uleb128(&mut self) -> Result<usize, Box<dyn Error>> {
+		let mut value = 0;
+		let mut shift = 0;
+
+		loop {
+			let byte = self.byte()?;
+			value |= ((byte & 0x7f) as usize) << shift;
+			shift += 7;
+
+			if byte & 0x80 == 0 {
+				return Ok(value);
+			}
+		}
+	}
+
+	/// Either 0x00 for no string, or 0x0b followed by the length and the UTF-8 bytes.
+	fn string(&mut self) -> Result<String, Box<dyn Error>> {
+		match self.byte()? {
+			0x00 => Ok(String::new()),
+			0x0b => {
+				let len = self.uleb128()?;
+				Ok(String::from_utf8(self.bytes(len)?.to_vec())?)
+			}
+			b => Err(format!("invalid string marker {:#04x} in the replay", b).into()),
+		}
+	}
+}
+
+/// Loads the key presses of a replay, the Nth lane being pressed with the Nth key of `lane_keys`.
+///
+/// Lanes are the columns in osu!mania, and K1, K2, M1 and M2 in the other modes.
+pub fn load(path: &Path, lane_keys: &[rdev::Key]) -> Result<Vec<TimedKeyEvent>, Box<dyn Error>> {
+	parse(&fs::read(path)?, lane_keys)
+}
+
+fn parse(data: &[u8], lane_keys: &[rdev::Key]) -> Result<Vec<TimedKeyEvent>, Box<dyn Error>> {
+	let mut reader = Reader { data };
+
+	let mode = reader.byte()?;
+	let _version = reader.int()?;
+	let _beatmap_hash = reader.string()?;
+	let _player = reader.string()?;
+	let _replay_hash = reader.string()?;
+
+	// 300s, 100s, 50s, gekis, katus, misses, score, max combo and perfect
+	for _ in 0..6 {
+		reader.short()?;
+	}
+	reader.int()?;
+	reader.short()?;
+	reader.byte()?;
+
+	let mods = reader.int()?;
+	let _life_bar = reader.string()?;
+	let _timestamp = reader.long()?;
+
+	let compressed_len = reader.int()? as usize;
+	let mut frames = Vec::new();
+	lzma_rs::lzma_decompress(&mut reader.bytes(compressed_len)?, &mut frames)?;
+
+	// frames are in the song's time, which goes faster or slower with these mods
+	let rate = match mods {
+		mods if mods & MOD_DOUBLE_TIME != 0 => 1.5,
+		mods if mods & MOD_HALF_TIME != 0 => 0.75,
+		_ => 1.,
+	};
+
+	let mut events = Vec::new();
+	let mut time = 0;
+	let mut prev_lanes = 0;
+
+	// frames are "w|x|y|z", w being the milliseconds since the previous frame
+	for frame in String::from_utf8(frames)?.split(',').filter(|frame| !frame.is_empty()) {
+		let fields = frame.split('|').collect::<Vec<_>>();
+		if fields.len() < 4 {
+			return Err(format!("invalid replay frame: {}", frame).into());
+		}
+
+		let delta = fields[0].parse::<i64>()?;
+		if delta == SEED_FRAME_TIME {
+			continue;
+		}
+
+		time += delta;
+
+		let lanes = match mode {
+			// mania puts the pressed columns in x
+			MODE_MANIA => fields[1].parse::<f64>()? as u32,
+			_ => {
+				let keys = fields[3].parse::<f64>()? as u32;
+				let (k1, k2) = (keys & K1 != 0, keys & K2 != 0);
+				let m1 = keys & M1 != 0 && !k1;
+				let m2 = keys & M2 != 0 && !k2;
+
+				[k1, k2, m1, m2]
+					.iter()
+					.enumerate()
+					.map(|(i, &pressed)| (pressed as u32) << i)
+					.sum()
+			}
+		};
+
+		let event_time = Duration::from_secs_f64(time.max(0) as f64 / 1000. / rate);
+		push_changes(&mut events, lane_keys, prev_lanes, lanes, event_time);
+		prev_lanes = lanes;
+	}
+
+	// the first frames may go back in time
+	events.sort_by_key(|event| event.time);
+
+	// nothing stays held after the end
+	let end = events.last().map(|event| event.time).unwrap_or_default();
+	push_changes(&mut events, lane_keys, prev_lanes, 0, end);
+
+	Ok(events)
+}
+
+fn push_changes(events: &mut Vec<TimedKeyEvent>, lane_keys: &[rdev::Key], prev: u32, lanes: u32, time: Duration) {
+	for (i, &key) in lane_keys.iter().enumerate().take(u32::BITS as usize) {
+		let pressed = lanes & (1 << i) != 0;
+
+		if pressed != (prev & (1 << i) != 0) {
+			events.push(TimedKeyEvent { time, key, pressed });
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use rdev::Key;
+
+	const MODE_STANDARD: u8 = 0;
+
+	fn string(out: &mut Vec<u8>, s: &str) {
+		out.extend([0x0b, s.len() as u8]);
+		out.extend(s.as_bytes());
+	}
+
+	/// A replay with the given frames, everything else being filler.
+	fn replay(mode: u8, mods: u32, frames: &str) -> Vec<u8> {
+		let mut data = vec![mode];
+		data.extend(20240101u32.to_le_bytes());
+		string(&mut data, "beatmap hash");
+		data.push(0x00);
+		string(&mut data, "replay hash");
+		data.extend([0; 6 * 2 + 4 + 2 + 1]);
+		data.extend(mods.to_le_bytes());
+		data.push(0x00);
+		data.extend(0u64.to_le_bytes());
+
+		let mut compressed = Vec::new();
+		lzma_rs::lzma_compress(&mut frames.as_bytes(), &mut compressed).unwrap();
+		data.extend((compressed.len() as u32).to_le_bytes());
+		data.extend(compressed);
+		data
+	}
+
+	fn events(replay: &[u8], lane_keys: &[Key]) -> Vec<(u64, Key, bool)> {
+		(parse(replay, lane_keys).unwrap().into_iter())
+			.map(|event| {
+				(
+					(event.time.as_secs_f64() * 1000.).round() as u64,
+					event.key,
+					event.pressed,
+				)
+			})
+			.collect()
+	}
+
+	#[test]
+	fn reader_decodes_uleb128_strings() {
+		let text = "a".repeat(200);
+		let mut data = vec![0x0b, 0xc8, 0x01];
+		data.extend(text.as_bytes());
+		data.push(0x00);
+
+		let mut reader = Reader { data: &data };
+		assert_eq!(reader.string().unwrap(), text);
+		assert_eq!(reader.string().unwrap(), "");
+		assert!(reader.byte().is_err());
+
+		assert!(Reader { data: &[0x42] }.string().is_err());
+		assert!(Reader { data: &[1, 2, 3] }.int().is_err());
+	}
+
+	#[test]
+	fn mania_columns_come_from_x() {
+		// the first two frames are at the start, and the last one holds the seed
+		let frames = "0|0|0|0,-1|0|0|0,100|1|0|0,50|3|0|0,50|2|0|0,-12345|0|0|7654321";
+		let replay = replay(MODE_MANIA, 0, frames);
+
+		assert_eq!(
+			events(&replay, &[Key::KeyD, Key::KeyF]),
+			[
+				(99, Key::KeyD, true),
+				(149, Key::KeyF, true),
+				(199, Key::KeyD, false),
+				(199, Key::KeyF, false),
+			]
+		);
+	}
+
+	#[test]
+	fn speed_mods_scale_the_times() {
+		let frames = "1500|1|0|0,1500|0|0|0";
+
+		let double_time = replay(MODE_MANIA, MOD_DOUBLE_TIME, frames);
+		assert_eq!(
+			events(&double_time, &[Key::KeyD]),
+			[(1000, Key::KeyD, true), (2000, Key::KeyD, false)]
+		);
+
+		let half_time = replay(MODE_MANIA, MOD_HALF_TIME, "750|1|0|0,750|0|0|0");
+		assert_eq!(
+			events(&half_time, &[Key::KeyD]),
+			[(1000, Key::KeyD, true), (2000, Key::KeyD, false)]
+		);
+	}
+
+	#[test]
+	fn k1_and_k2_mask_m1_and_m2() {
+		// K1 + M1, then M1 alone, then K2 + M2
+		let frames = "10|256|192|5,10|256|192|1,10|256|192|10";
+		let replay = replay(MODE_STANDARD, 0, frames);
+
+		assert_eq!(
+			events(&replay, &[Key::KeyZ, Key::KeyX, Key::KeyA, Key::KeyS]),
+			[
+				(10, Key::KeyZ, true),
+				(20, Key::KeyZ, false),
+				(20, Key::KeyA, true),
+				(30, Key::KeyX, true),
+				(30, Key::KeyA, false),
+				(30, Key::KeyX, false),
+			]
+		);
+	}
+}
@@ -0,0 +1,45 @@
+//! Plays recorded key events back in real time, as if they came from the keyboard
+
+use std::io;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::{InputEvent, KeyEvent};
+
+pub struct TimedKeyEvent {
+	/// Time of the event from the start of the recording
+	pub time: Duration,
+	pub key: rdev::Key,
+	pub pressed: bool,
+}
+
+/// Sends the events in the background at their time from now, in the order they're given.
+pub fn spawn_playback(events: Vec<TimedKeyEvent>) -> io::Result<mpsc::Receiver<InputEvent>> {
+	let (input_tx, input_rx) = mpsc::channel::<InputEvent>();
+
+	thread::Builder::new().name("Playback".to_string()).spawn(move || {
+		let start = SystemTime::now();
+
+		for event in events {
+			let time = start + event.time;
+
+			if let Ok(wait) = time.duration_since(SystemTime::now()) {
+				thread::sleep(wait);
+			}
+
+			let key_event = KeyEvent {
+				key: event.key,
+				pressed: event.pressed,
+				time,
+			};
+
+			// the window got closed
+			if input_tx.send(InputEvent::Key(key_event)).is_err() {
+				break;
+			}
+		}
+	})?;
+
+	Ok(input_rx)
+}
@@ -0,0 +1,366 @@
+//! Reaction time test, measuring the time between a visual cue and the next key press
+
+use std::collections::HashSet;
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use glam::Vec2;
+use loki_draw::drawer::{Drawer, RectBlueprint, TextBlueprint};
+use loki_draw::font::Font;
+use loki_draw::rect::Rect;
+use winit::keyboard::ModifiersState;
+
+use crate::config::ReactionProps;
+use crate::key::display_key;
+use crate::layout::{DETAIL_COLOR, LINE_GAP, TEXT_PADDING};
+use crate::texture::ImageDrawer;
+use crate::{InputEvent, KeyEvent, Scene, ROBOTO_FONT};
+
+const CUE_TEXT_COLOR: u32 = 0x111111;
+const BAR_COLOR: u32 = 0x63ffec;
+
+enum ReactionState {
+	Idle,
+	/// The cue shows up at `cue_at`, pressing before that is too early
+	Waiting {
+		cue_at: SystemTime,
+	},
+	/// The cue got drawn, starting from the first frame it was visible on
+	Cue {
+		shown_at: SystemTime,
+	},
+	Done,
+}
+
+/// What happened on the previous press.
+enum Outcome {
+	Reacted(Duration),
+	Early,
+}
+
+pub struct ReactionScene {
+	props: ReactionProps,
+	input_rx: mpsc::Receiver<InputEvent>,
+	default_font: Font<'static>,
+	now: SystemTime,
+	held_keys: HashSet<rdev::Key>,
+	/// State of a xorshift generator, for the delays
+	rng: u64,
+
+	state: ReactionState,
+	last_outcome: Option<Outcome>,
+	reactions: Vec<Duration>,
+	early_presses: u32,
+}
+
+impl ReactionScene {
+	pub fn new(input_rx: mpsc::Receiver<InputEvent>, props: ReactionProps) -> Self {
+		let now = SystemTime::now();
+		let seed = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+
+		Self {
+			props,
+			input_rx,
+			default_font: Font::from_data(ROBOTO_FONT),
+			now,
+			held_keys: HashSet::new(),
+			rng: seed | 1,
+
+			state: ReactionState::Idle,
+			last_outcome: None,
+			reactions: Vec::new(),
+			early_presses: 0,
+		}
+	}
+
+	/// Random number between 0 and 1.
+	fn random(&mut self) -> f32 {
+		self.rng ^= self.rng << 13;
+		self.rng ^= self.rng >> 7;
+		self.rng ^= self.rng << 17;
+		(self.rng >> 40) as f32 / (1 << 24) as f32
+	}
+
+	/// Schedules the next cue at a random delay after `from`.
+	fn start_round(&mut self, from: SystemTime) {
+		// the delays got checked along with the config
+		let (min_delay, max_delay) = (self.props.min_delay, self.props.max_delay);
+		let delay = min_delay + self.random() * (max_delay - min_delay);
+
+		self.state = ReactionState::Waiting {
+			cue_at: from + Duration::from_secs_f32(delay),
+		};
+	}
+
+	fn handle_key_event(&mut self, key_event: &KeyEvent) {
+		if !key_event.pressed {
+			self.held_keys.remove(&key_event.key);
+			return;
+		}
+
+		// key repeats aren't reactions
+		if !self.held_keys.insert(key_event.key) || !self.props.keys.contains(&key_event.key) {
+			return;
+		}
+
+		match self.state {
+			ReactionState::Idle | ReactionState::Done => {
+				self.last_outcome = None;
+				self.reactions.clear();
+				self.early_presses = 0;
+				self.start_round(key_event.time);
+			}
+			ReactionState::Cue { shown_at } if key_event.time >= shown_at => {
+				let reaction = key_event.time.duration_since(shown_at).unwrap_or_default();
+				self.reactions.push(reaction);
+				self.last_outcome = Some(Outcome::Reacted(reaction));
+
+				match self.reactions.len() as u32 >= self.props.rounds {
+					true => self.state = ReactionState::Done,
+					false => self.start_round(key_event.time),
+				}
+			}
+			// pressed before the cue was visible, the round starts over
+			ReactionState::Waiting { .. } | ReactionState::Cue { .. } => {
+				self.early_presses += 1;
+				self.last_outcome = Some(Outcome::Early);
+				self.start_round(key_event.time);
+			}
+		}
+	}
+
+	fn mean(&self) -> Duration {
+		match self.reactions.len() {
+			0 => Duration::ZERO,
+			n => self.reactions.iter().sum::<Duration>() / n as u32,
+		}
+	}
+
+	fn best(&self) -> Duration {
+		self.reactions.iter().copied().min().unwrap_or_default()
+	}
+
+	/// Number of reactions per bucket, from the bucket of the fastest one to the slowest one.
+	fn distribution(&self) -> (u64, Vec<u32>) {
+		let bucket = self.props.bucket.max(1);
+		let buckets = (self.reactions.iter())
+			.map(|reaction| reaction.as_millis() as u64 / bucket)
+			.collect::<Vec<_>>();
+
+		let (Some(&first), Some(&last)) = (buckets.iter().min(), buckets.iter().max()) else {
+			return (0, Vec::new());
+		};
+
+		let mut counts = vec![0; (last - first + 1) as usize];
+		for b in buckets {
+			counts[(b - first) as usize] += 1;
+		}
+
+		(first * bucket, counts)
+	}
+
+	fn keys_text(&self) -> String {
+		(self.props.keys.iter())
+			.map(|&key| display_key(key))
+			.collect::<Vec<_>>()
+			.join("/")
+	}
+
+	/// Text lines describing the state of the test.
+	fn status_lines(&self) -> Vec<(String, u32)> {
+		let outcome = self.last_outcome.as_ref().map(|outcome| match outcome {
+			Outcome::Reacted(reaction) => (format!("{} ms", reaction.as_millis()), self.props.color),
+			Outcome::Early => ("Too early!".to_string(), self.props.early_color),
+		});
+
+		let round = (
+			format!("Round {} / {}", self.reactions.len() + 1, self.props.rounds),
+			DETAIL_COLOR,
+		);
+
+		match self.state {
+			ReactionState::Idle => vec![(
+				format!("Press {} to start ({} rounds)", self.keys_text(), self.props.rounds),
+				self.props.color,
+			)],
+			ReactionState::Waiting { .. } => {
+				let mut lines = Vec::from_iter(outcome);
+				lines.push(("Wait for the cue...".to_string(), DETAIL_COLOR));
+				lines.push(round);
+				lines
+			}
+			ReactionState::Cue { .. } => vec![("Press!".to_string(), CUE_TEXT_COLOR)],
+			ReactionState::Done => vec![
+				(
+					format!(
+						"Mean {} ms · Best {} ms",
+						self.mean().as_millis(),
+						self.best().as_millis()
+					),
+					self.props.color,
+				),
+				(
+					format!("{} early presses", self.early_presses),
+					match self.early_presses {
+						0 => DETAIL_COLOR,
+						_ => self.props.early_color,
+					},
+				),
+				(format!("Press {} to retry", self.keys_text()), DETAIL_COLOR),
+			],
+		}
+	}
+
+	/// Draws the distribution of the reactions as bars between `top` and the bottom of the window.
+	fn draw_distribution(&self, viewport: Vec2, drawer: &mut impl Drawer, top: f32) {
+		let (first_bucket, counts) = self.distribution();
+		let max_count = counts.iter().copied().max().unwrap_or(0).max(1);
+
+		let label_size = self.props.font_size * 0.6;
+		let graph_height = viewport.y - top - 2. * TEXT_PADDING - label_size - LINE_GAP;
+		if counts.is_empty() || graph_height <= 0. {
+			return;
+		}
+
+		let bar_width = (viewport.x - 2. * TEXT_PADDING) / counts.len() as f32;
+
+		for (i, &count) in counts.iter().enumerate() {
+			let x = TEXT_PADDING + i as f32 * bar_width;
+			let bar_height = graph_height * count as f32 / max_count as f32;
+
+			if count > 0 {
+				drawer.draw_rect(&RectBlueprint {
+					rect: Rect::new(x, top + graph_height - bar_height, bar_width * 0.8, bar_height),
+					color: BAR_COLOR,
+					border_color: BAR_COLOR,
+					border_width: 0.,
+					corner_radius: 0.,
+					borders: [false, false, false, false],
+					alpha: 1.,
+				});
+			}
+
+			let label = format!("{}", first_bucket + i as u64 * self.props.bucket.max(1));
+			drawer.draw_text(&TextBlueprint {
+				text: &label,
+				x,
+				y: top + graph_height + LINE_GAP,
+				font: &self.default_font,
+				size: label_size,
+				col: DETAIL_COLOR,
+				alpha: 1.,
+			});
+		}
+	}
+}
+
+impl Scene for ReactionScene {
+	fn update(&mut self) {
+		self.now = SystemTime::now();
+
+		// the cue is about to be drawn, presses before this frame were too early
+		if let ReactionState::Waiting { cue_at } = self.state {
+			if self.now >= cue_at {
+				self.state = ReactionState::Cue { shown_at: self.now };
+			}
+		}
+
+		while let Ok(input_event) = self.input_rx.try_recv() {
+			if let InputEvent::Key(key_event) = &input_event {
+				self.handle_key_event(key_event);
+			}
+		}
+	}
+
+	fn resize(&mut self, _viewport: Vec2) {}
+
+	fn inapp_key_event(&mut self, _event: winit::event::KeyEvent, _modifiers: ModifiersState) {}
+
+	fn draw(&self, viewport: Vec2, drawer: &mut impl Drawer, _image_drawer: &mut impl ImageDrawer) {
+		let lines = self.status_lines();
+
+		drawer.clear();
+		drawer.begin_frame();
+		{
+			if let ReactionState::Cue { .. } = self.state {
+				drawer.draw_rect(&RectBlueprint {
+					rect: Rect::new(0., 0., viewport.x, viewport.y),
+					color: self.props.cue_color,
+					border_color: self.props.cue_color,
+					border_width: 0.,
+					corner_radius: 0.,
+					borders: [false, false, false, false],
+					alpha: 1.,
+				});
+			}
+
+			let mut y = TEXT_PADDING;
+
+			for (i, (text, col)) in lines.iter().enumerate() {
+				let text = TextBlueprint {
+					text,
+					x: TEXT_PADDING,
+					y,
+					font: &self.default_font,
+					// the first line is the one that matters
+					size: match i {
+						0 => 2. * self.props.font_size,
+						_ => self.props.font_size,
+					},
+					col: *col,
+					alpha: 1.,
+				};
+
+				drawer.draw_text(&text);
+				y += text.text_height() + LINE_GAP;
+			}
+
+			if let ReactionState::Done = self.state {
+				self.draw_distribution(viewport, drawer, y + TEXT_PADDING);
+			}
+		}
+		drawer.end_frame();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn scene(reactions: &[u64]) -> ReactionScene {
+		let (_, input_rx) = mpsc::channel();
+		let props = ReactionProps {
+			bucket: 25,
+			..ReactionProps::default()
+		};
+
+		let mut scene = ReactionScene::new(input_rx, props);
+		scene.reactions = reactions.iter().map(|&ms| Duration::from_millis(ms)).collect();
+		scene
+	}
+
+	#[test]
+	fn no_rounds() {
+		let scene = scene(&[]);
+		assert_eq!(scene.mean(), Duration::ZERO);
+		assert_eq!(scene.best(), Duration::ZERO);
+		assert_eq!(scene.distribution(), (0, vec![]));
+	}
+
+	#[test]
+	fn single_round() {
+		let scene = scene(&[212]);
+		assert_eq!(scene.mean(), Duration::from_millis(212));
+		assert_eq!(scene.best(), Duration::from_millis(212));
+		assert_eq!(scene.distribution(), (200, vec![1]));
+	}
+
+	#[test]
+	fn buckets_span_the_fastest_to_the_slowest_reaction() {
+		// bucket edges belong to the bucket they start
+		let scene = scene(&[290, 224, 199, 200, 225]);
+		assert_eq!(scene.mean(), Duration::from_micros(227_600));
+		assert_eq!(scene.best(), Duration::from_millis(199));
+		assert_eq!(scene.distribution(), (175, vec![1, 2, 1, 0, 1]));
+	}
+}
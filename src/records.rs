@@ -0,0 +1,53 @@
+//! Personal bests kept in the config directory, one TOML table per kind of test
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Best results of some test, by test variant.
+pub struct Records<T> {
+	path: PathBuf,
+	pub bests: HashMap<String, T>,
+}
+
+impl<T: Serialize + DeserializeOwned> Records<T> {
+	/// Loads the records at `path`, starting from nothing if there aren't any yet.
+	pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+		let bests = match fs::read_to_string(path) {
+			Ok(records) => toml::from_str(&records)?,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+			Err(e) => return Err(e.into()),
+		};
+
+		Ok(Self {
+			path: path.to_path_buf(),
+			bests,
+		})
+	}
+
+	/// Keeps `result` if it beats the current best of `variant`, returning whether it did.
+	pub fn submit(&mut self, variant: &str, result: T, is_better: impl Fn(&T, &T) -> bool) -> bool {
+		if let Some(best) = self.bests.get(variant) {
+			if !is_better(&result, best) {
+				return false;
+			}
+		}
+
+		self.bests.insert(variant.to_string(), result);
+
+		if let Err(e) = self.save() {
+			eprintln!("ERROR (records): couldn't save {}: {}", self.path.display(), e);
+		}
+
+		true
+	}
+
+	fn save(&self) -> Result<(), Box<dyn Error>> {
+		fs::write(&self.path, toml::to_string(&self.bests)?)?;
+		Ok(())
+	}
+}
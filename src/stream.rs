@@ -0,0 +1,162 @@
+//! Live BPM of the presses on the lanes, for streams in rhythm games
+
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+use crate::config::StreamProps;
+
+/// Presses are counted as 1/4 notes.
+const PRESSES_PER_BEAT: f32 = 4.;
+
+pub struct StreamMeter {
+	pub props: StreamProps,
+	/// Presses within the smoothing window, most recent first
+	presses: VecDeque<SystemTime>,
+	peak_bpm: f32,
+	/// Consecutive presses fast enough to be above the threshold
+	stream_length: u32,
+	/// Lane of the last press that got counted
+	last_lane: Option<usize>,
+}
+
+impl StreamMeter {
+	pub fn new(props: StreamProps) -> Self {
+		Self {
+			props,
+			presses: VecDeque::new(),
+			peak_bpm: 0.,
+			stream_length: 0,
+			last_lane: None,
+		}
+	}
+
+	/// Longest interval between two presses of a stream.
+	fn max_stream_interval(&self) -> Duration {
+		Duration::from_secs_f32(60. / (self.props.threshold.max(1.) * PRESSES_PER_BEAT))
+	}
+
+	/// Counts a press on `lane`, unless it continues the stream on the same lane as the previous one:
+	/// streams alternate between lanes, repeated presses of one lane don't make it faster.
+	pub fn press(&mut self, lane: usize, time: SystemTime) {
+		if self.last_lane == Some(lane) && self.stream_length(time) > 0 {
+			return;
+		}
+		self.last_lane = Some(lane);
+
+		let in_stream = (self.presses.front())
+			.is_some_and(|&last| time.duration_since(last).unwrap_or_default() <= self.max_stream_interval());
+
+		self.stream_length = match in_stream {
+			true => self.stream_length + 1,
+			false => 1,
+		};
+
+		self.presses.push_front(time);
+
+		let window = Duration::from_millis(self.props.smoothing_window);
+		while (self.presses.back()).is_some_and(|&press| time.duration_since(press).unwrap_or_default() > window) {
+			self.presses.pop_back();
+		}
+
+		self.peak_bpm = self.peak_bpm.max(self.bpm(time));
+	}
+
+	/// BPM over the smoothing window, dropping to zero once the presses stop.
+	pub fn bpm(&self, now: SystemTime) -> f32 {
+		let (Some(&last), Some(&first)) = (self.presses.front(), self.presses.back()) else {
+			return 0.;
+		};
+
+		let window = Duration::from_millis(self.props.smoothing_window);
+		if now.duration_since(last).unwrap_or_default() > window {
+			return 0.;
+		}
+
+		let span = last.duration_since(first).unwrap_or_default().as_secs_f32();
+		match span > 0. {
+			true => (self.presses.len() - 1) as f32 / span * 60. / PRESSES_PER_BEAT,
+			false => 0.,
+		}
+	}
+
+	/// Length of the current stream, zero once it's too late to continue it.
+	pub fn stream_length(&self, now: SystemTime) -> u32 {
+		match self.presses.front() {
+			Some(&last) if now.duration_since(last).unwrap_or_default() <= self.max_stream_interval() => {
+				self.stream_length
+			}
+			_ => 0,
+		}
+	}
+
+	pub fn hud_line(&self, now: SystemTime) -> String {
+		format!(
+			"{:.0} BPM · peak {:.0} · stream {}",
+			self.bpm(now),
+			self.peak_bpm,
+			self.stream_length(now)
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::at;
+
+	fn meter() -> StreamMeter {
+		StreamMeter::new(StreamProps {
+			smoothing_window: 1000,
+			threshold: 150.,
+			font_size: 18.,
+			color: 0xeeeeee,
+		})
+	}
+
+	#[test]
+	fn steady_stream() {
+		let mut meter = meter();
+		// 10 presses per second for two seconds, the window only keeping the last second
+		for (i, ms) in (0..=2000).step_by(100).enumerate() {
+			meter.press(i % 2, at(ms));
+		}
+
+		assert_eq!(meter.presses.len(), 11);
+		assert!((meter.bpm(at(2000)) - 150.).abs() < 0.01);
+		assert_eq!(meter.stream_length(at(2000)), 21);
+
+		// the stream can't be continued anymore, and the BPM drops once the window is over
+		assert_eq!(meter.stream_length(at(2200)), 0);
+		assert!(meter.bpm(at(2500)) > 0.);
+		assert_eq!(meter.bpm(at(3100)), 0.);
+		assert!((meter.peak_bpm - 150.).abs() < 0.01);
+	}
+
+	#[test]
+	fn slow_presses_restart_the_stream() {
+		let mut meter = meter();
+		for (i, ms) in [0, 100, 200, 700, 800].into_iter().enumerate() {
+			meter.press(i % 2, at(ms));
+		}
+
+		assert_eq!(meter.stream_length(at(800)), 2);
+		// 4 intervals over 800 ms
+		assert!((meter.bpm(at(800)) - 75.).abs() < 0.01);
+	}
+
+	#[test]
+	fn only_alternating_presses_count() {
+		let mut meter = meter();
+		for (lane, ms) in [(0, 0), (0, 50), (1, 100), (1, 150), (0, 200)] {
+			meter.press(lane, at(ms));
+		}
+
+		assert_eq!(meter.stream_length(at(200)), 3);
+		// 2 intervals over 200 ms
+		assert!((meter.bpm(at(200)) - 150.).abs() < 0.01);
+
+		// once the stream is over, the same lane can start the next one
+		meter.press(0, at(1000));
+		assert_eq!(meter.stream_length(at(1000)), 1);
+	}
+}
@@ -0,0 +1,370 @@
+//! Tapping speed test, played on top of the key columns
+
+use std::collections::HashSet;
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime};
+
+use glam::Vec2;
+use loki_draw::drawer::{Drawer, RectBlueprint, TextBlueprint};
+use loki_draw::font::Font;
+use loki_draw::rect::Rect;
+use serde::{Deserialize, Serialize};
+use winit::keyboard::ModifiersState;
+
+use crate::config::TapTestProps;
+use crate::key::display_key;
+use crate::layout::{DETAIL_COLOR, LINE_GAP, TEXT_PADDING};
+use crate::records::Records;
+use crate::texture::ImageDrawer;
+use crate::{InputEvent, KeyEvent, KeyOverlayScene, Scene, ROBOTO_FONT};
+
+const BEST_COLOR: u32 = 0xffec63;
+
+/// Taps per second are converted to BPM as 1/4 notes.
+const TAPS_PER_BEAT: f32 = 4.;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TapResult {
+	pub taps: u32,
+	/// Average BPM between the first and last taps
+	pub bpm: f32,
+	/// Most taps within a single second
+	pub burst: u32,
+	/// Standard deviation of the intervals between taps, in milliseconds
+	pub deviation: f32,
+}
+
+impl TapResult {
+	fn from_taps(taps: &[SystemTime]) -> Self {
+		let intervals = (taps.windows(2))
+			.map(|pair| pair[1].duration_since(pair[0]).unwrap_or_default().as_secs_f32())
+			.collect::<Vec<_>>();
+
+		let total = intervals.iter().sum::<f32>();
+		let mean = total / intervals.len().max(1) as f32;
+		let variance = intervals.iter().map(|i| (i - mean).powi(2)).sum::<f32>() / intervals.len().max(1) as f32;
+
+		let bpm = match total > 0. {
+			true => intervals.len() as f32 / total * 60. / TAPS_PER_BEAT,
+			false => 0.,
+		};
+
+		// sliding one second window over the taps
+		let mut burst = 0;
+		let mut start = 0;
+		for (end, tap) in taps.iter().enumerate() {
+			while tap.duration_since(taps[start]).unwrap_or_default() >= Duration::from_secs(1) {
+				start += 1;
+			}
+
+			burst = burst.max(end - start + 1);
+		}
+
+		Self {
+			taps: taps.len() as u32,
+			bpm,
+			burst: burst as u32,
+			deviation: variance.sqrt() * 1000.,
+		}
+	}
+}
+
+enum TapTestState {
+	Idle,
+	/// Waiting for the first tap, which starts the timer
+	Armed,
+	Running {
+		taps: Vec<SystemTime>,
+	},
+	Done {
+		result: TapResult,
+		new_best: bool,
+	},
+}
+
+pub struct TapTestScene {
+	props: TapTestProps,
+	input_rx: mpsc::Receiver<InputEvent>,
+	/// Sends the input events on to the overlay once the test has seen them
+	overlay_tx: mpsc::Sender<InputEvent>,
+	overlay: KeyOverlayScene,
+	tap_keys: HashSet<rdev::Key>,
+	held_keys: HashSet<rdev::Key>,
+	default_font: Font<'static>,
+	now: SystemTime,
+
+	state: TapTestState,
+	/// Name of the test in the records, like "10s" or "100 taps"
+	variant: String,
+	records: Records<TapResult>,
+}
+
+impl TapTestScene {
+	pub fn new(
+		input_rx: mpsc::Receiver<InputEvent>,
+		overlay_tx: mpsc::Sender<InputEvent>,
+		overlay: KeyOverlayScene,
+		props: TapTestProps,
+		tap_keys: HashSet<rdev::Key>,
+		records: Records<TapResult>,
+	) -> Self {
+		let variant = match props.taps {
+			Some(taps) => format!("{} taps", taps),
+			None => format!("{}s", props.duration),
+		};
+
+		Self {
+			props,
+			input_rx,
+			overlay_tx,
+			overlay,
+			tap_keys,
+			held_keys: HashSet::new(),
+			default_font: Font::from_data(ROBOTO_FONT),
+			now: SystemTime::now(),
+
+			state: TapTestState::Idle,
+			variant,
+			records,
+		}
+	}
+
+	fn handle_key_event(&mut self, key_event: &KeyEvent) {
+		if !key_event.pressed {
+			self.held_keys.remove(&key_event.key);
+			return;
+		}
+
+		// key repeats aren't taps
+		if !self.held_keys.insert(key_event.key) {
+			return;
+		}
+
+		let is_tap = self.tap_keys.contains(&key_event.key);
+		let time_limit = self.time_limit();
+
+		match &mut self.state {
+			TapTestState::Idle | TapTestState::Done { .. } if key_event.key == self.props.start_key => {
+				self.state = TapTestState::Armed;
+			}
+			TapTestState::Armed if is_tap => {
+				self.state = TapTestState::Running {
+					taps: vec![key_event.time],
+				};
+			}
+			TapTestState::Running { taps } if is_tap => {
+				// taps landing after the time limit are too late, whenever they're processed
+				if self.props.taps.is_none() && key_event.time > taps[0] + time_limit {
+					return;
+				}
+
+				taps.push(key_event.time);
+			}
+			_ => return,
+		}
+
+		if let TapTestState::Running { taps } = &self.state {
+			if self.props.taps.is_some_and(|limit| taps.len() as u32 >= limit) {
+				self.finish();
+			}
+		}
+	}
+
+	fn time_limit(&self) -> Duration {
+		Duration::from_secs_f32(self.props.duration)
+	}
+
+	fn finish(&mut self) {
+		let TapTestState::Running { taps } = &self.state else {
+			return;
+		};
+
+		let result = TapResult::from_taps(taps);
+		let new_best = (self.records).submit(&self.variant, result.clone(), |a, b| a.bpm > b.bpm);
+		self.state = TapTestState::Done { result, new_best };
+	}
+
+	/// Text lines describing the state of the test.
+	fn status_lines(&self) -> Vec<(String, u32)> {
+		let start_key = display_key(self.props.start_key);
+		let best = (self.records.bests.get(&self.variant)).map(|best| {
+			(
+				format!("Personal best: {:.0} BPM ({} taps)", best.bpm, best.taps),
+				BEST_COLOR,
+			)
+		});
+
+		match &self.state {
+			TapTestState::Idle => [(
+				format!("Press {} to start ({})", start_key, self.variant),
+				self.props.color,
+			)]
+			.into_iter()
+			.chain(best)
+			.collect(),
+			TapTestState::Armed => vec![(format!("Tap to start! ({})", self.variant), self.props.color)],
+			TapTestState::Running { taps } => {
+				let progress = match self.props.taps {
+					Some(limit) => format!("{} / {} taps", taps.len(), limit),
+					None => {
+						let elapsed = self.now.duration_since(taps[0]).unwrap_or_default();
+						let remaining = self.time_limit().saturating_sub(elapsed);
+						format!("{:.1}s left · {} taps", remaining.as_secs_f32(), taps.len())
+					}
+				};
+
+				let bpm = TapResult::from_taps(taps).bpm;
+				vec![(progress, self.props.color), (format!("{:.0} BPM", bpm), DETAIL_COLOR)]
+			}
+			TapTestState::Done { result, new_best } => {
+				let mut lines = vec![
+					(
+						format!("{} taps · {:.0} BPM", result.taps, result.bpm),
+						self.props.color,
+					),
+					(
+						format!(
+							"Best second: {} taps · Consistency: ±{:.1} ms",
+							result.burst, result.deviation
+						),
+						DETAIL_COLOR,
+					),
+				];
+
+				match new_best {
+					true => lines.push(("New personal best!".to_string(), BEST_COLOR)),
+					false => lines.extend(best),
+				}
+
+				lines.push((format!("Press {} to retry", start_key), DETAIL_COLOR));
+				lines
+			}
+		}
+	}
+}
+
+impl Scene for TapTestScene {
+	fn update(&mut self) {
+		while let Ok(input_event) = self.input_rx.try_recv() {
+			if let InputEvent::Key(key_event) = &input_event {
+				self.handle_key_event(key_event);
+			}
+
+			if let Err(e) = self.overlay_tx.send(input_event) {
+				eprintln!("ERROR (tx.send): {}", e);
+			}
+		}
+
+		self.overlay.update();
+		self.now = SystemTime::now();
+
+		if let TapTestState::Running { taps } = &self.state {
+			let timed_out = self.now.duration_since(taps[0]).unwrap_or_default() >= self.time_limit();
+			if self.props.taps.is_none() && timed_out {
+				self.finish();
+			}
+		}
+	}
+
+	fn resize(&mut self, viewport: Vec2) {
+		self.overlay.resize(viewport);
+	}
+
+	fn inapp_key_event(&mut self, event: winit::event::KeyEvent, modifiers: ModifiersState) {
+		self.overlay.inapp_key_event(event, modifiers);
+	}
+
+	fn draw(&self, viewport: Vec2, drawer: &mut impl Drawer, image_drawer: &mut impl ImageDrawer) {
+		drawer.clear();
+		drawer.begin_frame();
+		{
+			self.overlay.draw_overlay(viewport, drawer, image_drawer);
+
+			let lines = self.status_lines();
+			let text_blueprints = (lines.iter())
+				.map(|(text, col)| TextBlueprint {
+					text,
+					x: 0.,
+					y: 0.,
+					font: &self.default_font,
+					size: self.props.font_size,
+					col: *col,
+					alpha: 1.,
+				})
+				.collect::<Vec<_>>();
+
+			let width = text_blueprints.iter().map(|text| text.text_width()).fold(0., f32::max);
+			let height = text_blueprints
+				.iter()
+				.map(|text| text.text_height() + LINE_GAP)
+				.sum::<f32>()
+				- LINE_GAP;
+
+			// panel centered at the top of the window
+			let panel_x = (viewport.x - width) / 2. - TEXT_PADDING;
+			drawer.draw_rect(&RectBlueprint {
+				rect: Rect::new(
+					panel_x,
+					TEXT_PADDING,
+					width + 2. * TEXT_PADDING,
+					height + 2. * TEXT_PADDING,
+				),
+				color: self.props.background_color,
+				border_color: self.props.background_color,
+				border_width: 0.,
+				corner_radius: TEXT_PADDING,
+				borders: [false, false, false, false],
+				alpha: self.props.alpha,
+			});
+
+			let mut y = 2. * TEXT_PADDING;
+			for mut text in text_blueprints {
+				text.x = (viewport.x - text.text_width()) / 2.;
+				text.y = y;
+				drawer.draw_text(&text);
+				y += text.text_height() + LINE_GAP;
+			}
+		}
+		drawer.end_frame();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::at;
+
+	fn taps(times: &[u64]) -> Vec<SystemTime> {
+		times.iter().map(|&ms| at(ms)).collect()
+	}
+
+	#[test]
+	fn steady_taps() {
+		let result = TapResult::from_taps(&taps(&[0, 100, 200, 300, 400, 500, 600, 700, 800, 900, 1000]));
+
+		assert_eq!(result.taps, 11);
+		// 10 taps per second, at 4 taps per beat
+		assert!((result.bpm - 150.).abs() < 0.01);
+		// the tap at 1000 ms is a second after the first one, so outside of its window
+		assert_eq!(result.burst, 10);
+		assert!(result.deviation < 0.01);
+	}
+
+	#[test]
+	fn uneven_taps() {
+		let result = TapResult::from_taps(&taps(&[0, 100, 300]));
+
+		assert!((result.bpm - 100.).abs() < 0.01);
+		assert_eq!(result.burst, 3);
+		assert!((result.deviation - 50.).abs() < 0.01);
+	}
+
+	#[test]
+	fn single_tap() {
+		let result = TapResult::from_taps(&taps(&[500]));
+
+		assert_eq!((result.taps, result.burst), (1, 1));
+		assert_eq!(result.bpm, 0.);
+		assert_eq!(result.deviation, 0.);
+	}
+}
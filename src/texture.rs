@@ -0,0 +1,290 @@
+//! Minimal textured quad rendering, for things loki-draw can't draw by itself (like key icons)
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+use std::ptr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use gl::types::{GLboolean, GLenum, GLint, GLuint};
+use glam::Vec2;
+use loki_draw::rect::Rect;
+
+const VERTEX_SHADER: &str = r#"#version 330 core
+layout (location = 0) in vec2 a_pos;
+
+uniform vec4 u_rect;
+uniform vec2 u_viewport;
+
+out vec2 v_uv;
+
+void main() {
+	vec2 pos = (u_rect.xy + a_pos * u_rect.zw) / u_viewport * 2.0 - 1.0;
+	gl_Position = vec4(pos.x, -pos.y, 0.0, 1.0);
+	v_uv = a_pos;
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"#version 330 core
+in vec2 v_uv;
+
+uniform sampler2D u_texture;
+uniform vec4 u_tint;
+
+out vec4 frag_color;
+
+void main() {
+	frag_color = texture(u_texture, v_uv) * u_tint;
+}
+"#;
+
+static NEXT_IMAGE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// RGBA image decoded in memory, uploaded to the GPU the first time it gets drawn.
+#[derive(Debug, Clone)]
+pub struct Image {
+	id: u64,
+	pub width: u32,
+	pub height: u32,
+	pixels: Vec<u8>,
+}
+
+impl Image {
+	pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+		let rgba = image::open(path)
+			.map_err(|e| format!("couldn't load image {}: {}", path.display(), e))?
+			.to_rgba8();
+
+		Ok(Self {
+			id: NEXT_IMAGE_ID.fetch_add(1, Ordering::Relaxed),
+			width: rgba.width(),
+			height: rgba.height(),
+			pixels: rgba.into_raw(),
+		})
+	}
+
+	pub fn size(&self) -> Vec2 {
+		Vec2::new(self.width as f32, self.height as f32)
+	}
+}
+
+pub struct ImageBlueprint<'a> {
+	pub image: &'a Image,
+	pub rect: Rect,
+	pub tint: u32,
+	pub alpha: f32,
+}
+
+pub trait ImageDrawer {
+	fn draw_image(&mut self, image: &ImageBlueprint);
+}
+
+pub struct TextureDrawer {
+	program: GLuint,
+	vao: GLuint,
+	u_rect: GLint,
+	u_viewport: GLint,
+	u_tint: GLint,
+	textures: HashMap<u64, GLuint>,
+	viewport: Vec2,
+}
+
+impl TextureDrawer {
+	/// Creates the drawer. An OpenGL context must be current.
+	pub fn new(width: u32, height: u32, dpi: f32) -> Self {
+		#[rustfmt::skip]
+		const QUAD: [f32; 12] = [
+			0., 0.,  1., 0.,  1., 1.,
+			0., 0.,  1., 1.,  0., 1.,
+		];
+
+		unsafe {
+			let program = link_program(VERTEX_SHADER, FRAGMENT_SHADER);
+
+			let mut vao = 0;
+			let mut vbo = 0;
+			gl::GenVertexArrays(1, &mut vao);
+			gl::GenBuffers(1, &mut vbo);
+
+			let state = GlState::save();
+
+			gl::BindVertexArray(vao);
+			gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+			gl::BufferData(
+				gl::ARRAY_BUFFER,
+				std::mem::size_of_val(&QUAD) as isize,
+				QUAD.as_ptr().cast(),
+				gl::STATIC_DRAW,
+			);
+			gl::VertexAttribPointer(
+				0,
+				2,
+				gl::FLOAT,
+				gl::FALSE,
+				2 * std::mem::size_of::<f32>() as i32,
+				ptr::null(),
+			);
+			gl::EnableVertexAttribArray(0);
+
+			state.restore();
+
+			Self {
+				program,
+				vao,
+				u_rect: gl::GetUniformLocation(program, c"u_rect".as_ptr()),
+				u_viewport: gl::GetUniformLocation(program, c"u_viewport".as_ptr()),
+				u_tint: gl::GetUniformLocation(program, c"u_tint".as_ptr()),
+				textures: HashMap::new(),
+				viewport: Vec2::new(width as f32, height as f32) / dpi,
+			}
+		}
+	}
+
+	pub fn resize(&mut self, viewport: Vec2, dpi: f32) {
+		self.viewport = viewport / dpi;
+	}
+
+	fn texture(&mut self, image: &Image) -> GLuint {
+		*self.textures.entry(image.id).or_insert_with(|| unsafe {
+			let mut texture = 0;
+			gl::GenTextures(1, &mut texture);
+			gl::BindTexture(gl::TEXTURE_2D, texture);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+			gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+			gl::TexImage2D(
+				gl::TEXTURE_2D,
+				0,
+				gl::RGBA as GLint,
+				image.width as i32,
+				image.height as i32,
+				0,
+				gl::RGBA,
+				gl::UNSIGNED_BYTE,
+				image.pixels.as_ptr().cast(),
+			);
+			texture
+		})
+	}
+}
+
+impl ImageDrawer for TextureDrawer {
+	fn draw_image(&mut self, image: &ImageBlueprint) {
+		unsafe {
+			// loki-draw owns the rest of the GL state, so leave it as we found it
+			let state = GlState::save();
+
+			let texture = self.texture(image.image);
+
+			let r = ((image.tint >> 16) & 0xff) as f32 / 255.;
+			let g = ((image.tint >> 8) & 0xff) as f32 / 255.;
+			let b = (image.tint & 0xff) as f32 / 255.;
+
+			gl::Enable(gl::BLEND);
+			gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+			gl::UseProgram(self.program);
+			gl::Uniform4f(self.u_rect, image.rect.x, image.rect.y, image.rect.w, image.rect.h);
+			gl::Uniform2f(self.u_viewport, self.viewport.x, self.viewport.y);
+			gl::Uniform4f(self.u_tint, r, g, b, image.alpha);
+
+			gl::ActiveTexture(gl::TEXTURE0);
+			gl::BindTexture(gl::TEXTURE_2D, texture);
+			gl::BindVertexArray(self.vao);
+			gl::DrawArrays(gl::TRIANGLES, 0, 6);
+
+			state.restore();
+		}
+	}
+}
+
+/// Bits of GL state touched by the texture drawer.
+struct GlState {
+	program: GLint,
+	vao: GLint,
+	array_buffer: GLint,
+	active_texture: GLint,
+	texture: GLint,
+	blend: GLboolean,
+}
+
+impl GlState {
+	unsafe fn save() -> Self {
+		let mut state = Self {
+			program: 0,
+			vao: 0,
+			array_buffer: 0,
+			active_texture: 0,
+			texture: 0,
+			blend: gl::IsEnabled(gl::BLEND),
+		};
+
+		gl::GetIntegerv(gl::CURRENT_PROGRAM, &mut state.program);
+		gl::GetIntegerv(gl::VERTEX_ARRAY_BINDING, &mut state.vao);
+		gl::GetIntegerv(gl::ARRAY_BUFFER_BINDING, &mut state.array_buffer);
+		gl::GetIntegerv(gl::ACTIVE_TEXTURE, &mut state.active_texture);
+		gl::ActiveTexture(gl::TEXTURE0);
+		gl::GetIntegerv(gl::TEXTURE_BINDING_2D, &mut state.texture);
+
+		state
+	}
+
+	unsafe fn restore(&self) {
+		gl::ActiveTexture(gl::TEXTURE0);
+		gl::BindTexture(gl::TEXTURE_2D, self.texture as GLuint);
+		gl::ActiveTexture(self.active_texture as GLenum);
+		gl::BindVertexArray(self.vao as GLuint);
+		gl::BindBuffer(gl::ARRAY_BUFFER, self.array_buffer as GLuint);
+		gl::UseProgram(self.program as GLuint);
+
+		if self.blend == gl::FALSE {
+			gl::Disable(gl::BLEND);
+		}
+	}
+}
+
+unsafe fn compile_shader(kind: GLenum, source: &str) -> GLuint {
+	let shader = gl::CreateShader(kind);
+	let len = source.len() as GLint;
+	gl::ShaderSource(shader, 1, &source.as_ptr().cast(), &len);
+	gl::CompileShader(shader);
+
+	let mut success = 0;
+	gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
+	if success == 0 {
+		let mut log = vec![0u8; 1024];
+		let mut log_len = 0;
+		gl::GetShaderInfoLog(shader, log.len() as i32, &mut log_len, log.as_mut_ptr().cast());
+		log.truncate(log_len as usize);
+		panic!("couldn't compile texture shader: {}", String::from_utf8_lossy(&log));
+	}
+
+	shader
+}
+
+unsafe fn link_program(vertex_source: &str, fragment_source: &str) -> GLuint {
+	let vertex_shader = compile_shader(gl::VERTEX_SHADER, vertex_source);
+	let fragment_shader = compile_shader(gl::FRAGMENT_SHADER, fragment_source);
+
+	let program = gl::CreateProgram();
+	gl::AttachShader(program, vertex_shader);
+	gl::AttachShader(program, fragment_shader);
+	gl::LinkProgram(program);
+
+	let mut success = 0;
+	gl::GetProgramiv(program, gl::LINK_STATUS, &mut success);
+	if success == 0 {
+		let mut log = vec![0u8; 1024];
+		let mut log_len = 0;
+		gl::GetProgramInfoLog(program, log.len() as i32, &mut log_len, log.as_mut_ptr().cast());
+		log.truncate(log_len as usize);
+		panic!("couldn't link texture shader: {}", String::from_utf8_lossy(&log));
+	}
+
+	gl::DeleteShader(vertex_shader);
+	gl::DeleteShader(fragment_shader);
+
+	program
+}
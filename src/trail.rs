@@ -0,0 +1,84 @@
+use std::collections::VecDeque;
+use std::time::SystemTime;
+
+const MAX_TRAIL_TIMES: usize = 1024;
+
+/// Press and release times of a lane, most recent first.
+#[derive(Debug, Clone)]
+pub struct Trail {
+	times: VecDeque<SystemTime>,
+}
+
+impl Trail {
+	pub fn new() -> Self {
+		Self {
+			times: VecDeque::with_capacity(MAX_TRAIL_TIMES),
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.times.len()
+	}
+
+	pub fn push(&mut self, time: SystemTime) {
+		if self.times.len() >= MAX_TRAIL_TIMES {
+			self.times.pop_back();
+		}
+
+		self.times.push_front(time);
+	}
+
+	/// Held segments as `(press, release)` pairs, most recent first.
+	///
+	/// If the lane is still `pressed`, its last segment is released at `now`.
+	pub fn segments(&self, pressed: bool, now: SystemTime) -> impl Iterator<Item = (SystemTime, SystemTime)> + '_ {
+		let mut times = self.times.iter().copied();
+		let mut held_release = pressed.then_some(now);
+
+		std::iter::from_fn(move || {
+			let release = held_release.take().or_else(|| times.next())?;
+			let press = times.next()?;
+			Some((press, release))
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::at;
+
+	fn trail(times: &[u64]) -> Trail {
+		let mut trail = Trail::new();
+		for &ms in times {
+			trail.push(at(ms));
+		}
+		trail
+	}
+
+	#[test]
+	fn released_segments_pair_up() {
+		let trail = trail(&[100, 200, 300, 400]);
+		let segments = trail.segments(false, at(500)).collect::<Vec<_>>();
+		assert_eq!(segments, [(at(300), at(400)), (at(100), at(200))]);
+	}
+
+	#[test]
+	fn held_segment_ends_now() {
+		let trail = trail(&[100, 200, 300]);
+		let segments = trail.segments(true, at(500)).collect::<Vec<_>>();
+		assert_eq!(segments, [(at(300), at(500)), (at(100), at(200))]);
+	}
+
+	#[test]
+	fn oldest_times_get_dropped() {
+		let mut trail = Trail::new();
+		for ms in 0..MAX_TRAIL_TIMES as u64 + 2 {
+			trail.push(at(ms));
+		}
+
+		assert_eq!(trail.len(), MAX_TRAIL_TIMES);
+		let oldest = trail.segments(false, at(0)).last().unwrap();
+		assert_eq!(oldest, (at(2), at(3)));
+	}
+}
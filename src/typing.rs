@@ -0,0 +1,227 @@
+//! Typing speed HUD, with words and characters per minute from the global key stream
+
+use std::collections::{HashSet, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use glam::Vec2;
+use loki_draw::drawer::{Drawer, TextBlueprint};
+use loki_draw::font::Font;
+use winit::keyboard::ModifiersState;
+
+use crate::config::TypingProps;
+use crate::key::is_character;
+use crate::layout::{DETAIL_COLOR, LINE_GAP, TEXT_PADDING};
+use crate::texture::ImageDrawer;
+use crate::{InputEvent, KeyEvent, Scene, ROBOTO_FONT};
+
+const CHARS_PER_WORD: f32 = 5.;
+const RESULTS_HEADER: &str = "end_time,active_seconds,characters,backspaces,wpm,cpm,backspace_ratio";
+
+/// Keys that turn a press into a shortcut rather than typing.
+const SHORTCUT_MODIFIERS: [rdev::Key; 5] = [
+	rdev::Key::ControlLeft,
+	rdev::Key::ControlRight,
+	rdev::Key::Alt,
+	rdev::Key::MetaLeft,
+	rdev::Key::MetaRight,
+];
+
+pub struct TypingScene {
+	props: TypingProps,
+	input_rx: mpsc::Receiver<InputEvent>,
+	default_font: Font<'static>,
+	now: SystemTime,
+	held_keys: HashSet<rdev::Key>,
+
+	/// Times of the characters typed within the rolling window, most recent first
+	recent_chars: VecDeque<SystemTime>,
+	characters: u64,
+	backspaces: u64,
+	last_keystroke: Option<SystemTime>,
+	/// Time spent typing, pauses longer than the idle timeout not counting
+	active_time: Duration,
+}
+
+impl TypingScene {
+	pub fn new(input_rx: mpsc::Receiver<InputEvent>, props: TypingProps) -> Self {
+		Self {
+			props,
+			input_rx,
+			default_font: Font::from_data(ROBOTO_FONT),
+			now: SystemTime::now(),
+			held_keys: HashSet::new(),
+
+			recent_chars: VecDeque::new(),
+			characters: 0,
+			backspaces: 0,
+			last_keystroke: None,
+			active_time: Duration::ZERO,
+		}
+	}
+
+	fn handle_key_event(&mut self, key_event: KeyEvent) {
+		if !key_event.pressed {
+			self.held_keys.remove(&key_event.key);
+			return;
+		}
+
+		// key repeats aren't new keystrokes
+		if !self.held_keys.insert(key_event.key) {
+			return;
+		}
+
+		if SHORTCUT_MODIFIERS.iter().any(|key| self.held_keys.contains(key)) {
+			return;
+		}
+
+		match key_event.key {
+			rdev::Key::Backspace => self.backspaces += 1,
+			key if is_character(key) => {
+				self.characters += 1;
+				self.recent_chars.push_front(key_event.time);
+			}
+			_ => return,
+		}
+
+		if let Some(last_keystroke) = self.last_keystroke {
+			let idle_timeout = Duration::from_secs_f32(self.props.idle_timeout);
+			let gap = key_event.time.duration_since(last_keystroke).unwrap_or_default();
+			self.active_time += gap.min(idle_timeout);
+		}
+
+		self.last_keystroke = Some(key_event.time);
+	}
+
+	/// Characters per minute over the rolling window.
+	fn rolling_cpm(&self) -> f32 {
+		let window = match self.active_time.as_secs_f32() {
+			// the window can't be longer than the session itself
+			active if active > 0. => self.props.rolling_window.min(active),
+			_ => return 0.,
+		};
+
+		self.recent_chars.len() as f32 / window * 60.
+	}
+
+	/// Characters per minute over the whole session.
+	fn session_cpm(&self) -> f32 {
+		match self.active_time.as_secs_f32() {
+			active if active > 0. => self.characters as f32 / active * 60.,
+			_ => 0.,
+		}
+	}
+
+	/// Share of keystrokes that were backspaces.
+	fn backspace_ratio(&self) -> f32 {
+		match self.characters + self.backspaces {
+			0 => 0.,
+			keystrokes => self.backspaces as f32 / keystrokes as f32,
+		}
+	}
+
+	fn write_results(&self) -> io::Result<()> {
+		let Some(results_file) = &self.props.results_file else {
+			return Ok(());
+		};
+
+		if self.characters == 0 {
+			return Ok(());
+		}
+
+		let write_header = fs::metadata(results_file).is_err();
+		let mut file = OpenOptions::new().create(true).append(true).open(results_file)?;
+
+		if write_header {
+			writeln!(file, "{}", RESULTS_HEADER)?;
+		}
+
+		writeln!(
+			file,
+			"{},{:.1},{},{},{:.1},{:.1},{:.3}",
+			self.now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+			self.active_time.as_secs_f32(),
+			self.characters,
+			self.backspaces,
+			self.session_cpm() / CHARS_PER_WORD,
+			self.session_cpm(),
+			self.backspace_ratio(),
+		)
+	}
+}
+
+impl Scene for TypingScene {
+	fn update(&mut self) {
+		while let Ok(input_event) = self.input_rx.try_recv() {
+			if let InputEvent::Key(key_event) = input_event {
+				self.handle_key_event(key_event);
+			}
+		}
+
+		self.now = SystemTime::now();
+
+		let window = Duration::from_secs_f32(self.props.rolling_window);
+		while (self.recent_chars.back()).is_some_and(|&time| self.now.duration_since(time).unwrap_or_default() > window)
+		{
+			self.recent_chars.pop_back();
+		}
+	}
+
+	fn resize(&mut self, _viewport: Vec2) {}
+
+	fn inapp_key_event(&mut self, _event: winit::event::KeyEvent, _modifiers: ModifiersState) {}
+
+	fn exit(&mut self) {
+		if let Err(e) = self.write_results() {
+			eprintln!("ERROR (typing results): {}", e);
+		}
+	}
+
+	fn draw(&self, _viewport: Vec2, drawer: &mut impl Drawer, _image_drawer: &mut impl ImageDrawer) {
+		let rolling_cpm = self.rolling_cpm();
+		let session_cpm = self.session_cpm();
+
+		let lines = [
+			(
+				format!("{:.0} WPM", rolling_cpm / CHARS_PER_WORD),
+				2. * self.props.font_size,
+				self.props.color,
+			),
+			(
+				format!("avg {:.0} WPM", session_cpm / CHARS_PER_WORD),
+				self.props.font_size,
+				self.props.color,
+			),
+			(format!("{:.0} CPM", rolling_cpm), self.props.font_size, DETAIL_COLOR),
+			(
+				format!("{:.1}% backspaces", self.backspace_ratio() * 100.),
+				self.props.font_size,
+				DETAIL_COLOR,
+			),
+		];
+
+		drawer.clear();
+		drawer.begin_frame();
+		{
+			let mut y = TEXT_PADDING;
+
+			for (text, size, col) in &lines {
+				let text = TextBlueprint {
+					text,
+					x: TEXT_PADDING,
+					y,
+					font: &self.default_font,
+					size: *size,
+					col: *col,
+					alpha: 1.,
+				};
+
+				drawer.draw_text(&text);
+				y += text.text_height() + LINE_GAP;
+			}
+		}
+		drawer.end_frame();
+	}
+}
@@ -0,0 +1,114 @@
+//! Standalone panels drawn next to the key columns, configured with `[[widgets]]`
+
+use std::error::Error;
+use std::time::SystemTime;
+
+use glam::Vec2;
+use loki_draw::drawer::Drawer;
+use loki_draw::font::Font;
+use loki_draw::rect::Rect;
+
+use crate::config::{WidgetProps, WidgetRect};
+use crate::KeyEvent;
+
+mod apm;
+mod dpad;
+mod input_history;
+mod mouse;
+
+pub use apm::Apm;
+pub use dpad::Dpad;
+pub use input_history::InputHistory;
+pub use mouse::Mouse;
+
+/// Distance between the content of the widgets and their edge
+const PADDING: f32 = 4.;
+
+pub enum Widget {
+	InputHistory(InputHistory),
+	Dpad(Dpad),
+	Mouse(Mouse),
+	Apm(Apm),
+}
+
+impl Widget {
+	pub fn new(props: WidgetProps) -> Result<Self, Box<dyn Error>> {
+		Ok(match props {
+			WidgetProps::InputHistory(props) => Self::InputHistory(InputHistory::new(props)?),
+			WidgetProps::Dpad(props) => Self::Dpad(Dpad::new(props)),
+			WidgetProps::Mouse(props) => Self::Mouse(Mouse::new(props)),
+			WidgetProps::Apm(props) => Self::Apm(Apm::new(props)),
+		})
+	}
+
+	/// Keys the widget needs to hear about.
+	pub fn keys(&self) -> Vec<rdev::Key> {
+		match self {
+			Self::InputHistory(widget) => widget.keys(),
+			Self::Dpad(widget) => widget.keys(),
+			Self::Mouse(_) => Vec::new(),
+			Self::Apm(widget) => widget.keys(),
+		}
+	}
+
+	pub fn listens_to_all_keys(&self) -> bool {
+		match self {
+			Self::Apm(widget) => widget.listens_to_all_keys(),
+			_ => false,
+		}
+	}
+
+	pub fn listens_to_mouse_moves(&self) -> bool {
+		matches!(self, Self::Mouse(_))
+	}
+
+	pub fn listens_to_mouse_buttons(&self) -> bool {
+		match self {
+			Self::Apm(widget) => widget.listens_to_mouse_buttons(),
+			_ => false,
+		}
+	}
+
+	pub fn key_event(&mut self, event: &KeyEvent) {
+		match self {
+			Self::InputHistory(widget) => widget.key_event(event),
+			Self::Dpad(widget) => widget.key_event(event),
+			Self::Mouse(_) => (),
+			Self::Apm(widget) => widget.key_event(event),
+		}
+	}
+
+	/// Handles the cursor moving to `pos`, in screen pixels.
+	pub fn mouse_move(&mut self, pos: Vec2, time: SystemTime) {
+		if let Self::Mouse(widget) = self {
+			widget.mouse_move(pos, time);
+		}
+	}
+
+	pub fn mouse_button(&mut self, button: rdev::Button, pressed: bool, time: SystemTime) {
+		if let Self::Apm(widget) = self {
+			widget.mouse_button(button, pressed, time);
+		}
+	}
+
+	/// Draws the widget, returning the number of rectangles and texts drawn.
+	pub fn draw(&self, drawer: &mut impl Drawer, font: &Font, scale: Vec2, now: SystemTime) -> (usize, usize) {
+		match self {
+			Self::InputHistory(widget) => widget.draw(drawer, font, scale, now),
+			Self::Dpad(widget) => widget.draw(drawer, scale, now),
+			Self::Mouse(widget) => widget.draw(drawer, font, scale, now),
+			Self::Apm(widget) => widget.draw(drawer, font, scale, now),
+		}
+	}
+}
+
+impl WidgetRect {
+	pub fn to_rect(self, scale: Vec2) -> Rect {
+		Rect::new(
+			self.x as f32 * scale.x,
+			self.y as f32 * scale.y,
+			self.width as f32 * scale.x,
+			self.height as f32 * scale.y,
+		)
+	}
+}
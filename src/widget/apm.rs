@@ -0,0 +1,230 @@
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, SystemTime};
+
+use glam::Vec2;
+use loki_draw::drawer::{Drawer, RectBlueprint, TextBlueprint};
+use loki_draw::font::Font;
+use loki_draw::rect::Rect;
+
+use crate::config::ApmProps;
+use crate::layout::DETAIL_COLOR;
+use crate::widget::PADDING;
+use crate::KeyEvent;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Input {
+	Key(rdev::Key),
+	Button(rdev::Button),
+}
+
+struct Action {
+	input: Input,
+	time: SystemTime,
+	/// Whether the action wasn't spam, counting towards EAPM
+	effective: bool,
+}
+
+/// Actions per minute, with spam filtered out for EAPM.
+pub struct Apm {
+	props: ApmProps,
+	held_keys: HashSet<rdev::Key>,
+	/// Actions within the rolling window and the graph, most recent first
+	actions: VecDeque<Action>,
+	first_action: Option<SystemTime>,
+	total_actions: u64,
+	total_effective: u64,
+}
+
+impl Apm {
+	pub fn new(props: ApmProps) -> Self {
+		Self {
+			props,
+			held_keys: HashSet::new(),
+			actions: VecDeque::new(),
+			first_action: None,
+			total_actions: 0,
+			total_effective: 0,
+		}
+	}
+
+	pub fn keys(&self) -> Vec<rdev::Key> {
+		self.props.keys.clone()
+	}
+
+	/// Without configured keys, every key counts.
+	pub fn listens_to_all_keys(&self) -> bool {
+		self.props.keys.is_empty()
+	}
+
+	pub fn listens_to_mouse_buttons(&self) -> bool {
+		!self.props.mouse_buttons.is_empty()
+	}
+
+	pub fn key_event(&mut self, event: &KeyEvent) {
+		if !event.pressed {
+			self.held_keys.remove(&event.key);
+			return;
+		}
+
+		// key repeats aren't actions
+		let counts = self.listens_to_all_keys() || self.props.keys.contains(&event.key);
+		if self.held_keys.insert(event.key) && counts {
+			self.push(Input::Key(event.key), event.time);
+		}
+	}
+
+	pub fn mouse_button(&mut self, button: rdev::Button, pressed: bool, time: SystemTime) {
+		if pressed && self.props.mouse_buttons.contains(&button) {
+			self.push(Input::Button(button), time);
+		}
+	}
+
+	fn push(&mut self, input: Input, time: SystemTime) {
+		let spam_window = Duration::from_millis(self.props.spam_window);
+		let effective = !(self.actions.front()).is_some_and(|last| {
+			last.input == input && time.duration_since(last.time).unwrap_or_default() < spam_window
+		});
+
+		let kept_duration = Duration::from_secs_f32(self.props.rolling_window.max(self.props.graph_duration));
+		while (self.actions.back())
+			.is_some_and(|action| time.duration_since(action.time).unwrap_or_default() > kept_duration)
+		{
+			self.actions.pop_back();
+		}
+
+		self.actions.push_front(Action { input, time, effective });
+		self.first_action.get_or_insert(time);
+		self.total_actions += 1;
+		self.total_effective += effective as u64;
+	}
+
+	/// APM and EAPM of the actions done between `duration` ago and `end`.
+	fn apm_between(&self, end: SystemTime, duration: f32) -> (f32, f32) {
+		let (mut actions, mut effective) = (0, 0);
+
+		for action in &self.actions {
+			let age = end.duration_since(action.time).map(|age| age.as_secs_f32());
+
+			match age {
+				Ok(age) if age > duration => break,
+				Ok(_) => {
+					actions += 1;
+					effective += action.effective as u32;
+				}
+				// newer than the end
+				Err(_) => (),
+			}
+		}
+
+		let per_minute = 60. / duration.max(f32::EPSILON);
+		(actions as f32 * per_minute, effective as f32 * per_minute)
+	}
+
+	/// APM and EAPM over the whole session.
+	fn session_apm(&self, now: SystemTime) -> (f32, f32) {
+		let minutes = match self.first_action {
+			Some(first_action) => now.duration_since(first_action).unwrap_or_default().as_secs_f32() / 60.,
+			None => 0.,
+		};
+
+		match minutes > 0. {
+			true => (
+				self.total_actions as f32 / minutes,
+				self.total_effective as f32 / minutes,
+			),
+			false => (0., 0.),
+		}
+	}
+
+	pub fn draw(&self, drawer: &mut impl Drawer, font: &Font, scale: Vec2, now: SystemTime) -> (usize, usize) {
+		let mut drawn_rects = 0;
+		let mut drawn_texts = 0;
+
+		let rect = self.props.rect.to_rect(scale);
+		let padding = PADDING * scale.y;
+		let font_size = self.props.font_size * scale.y;
+
+		drawer.draw_rect(&RectBlueprint {
+			rect: self.props.rect.to_rect(scale),
+			color: self.props.background_color,
+			border_color: self.props.background_color,
+			border_width: 0.,
+			corner_radius: 2.,
+			borders: [false, false, false, false],
+			alpha: self.props.alpha,
+		});
+		drawn_rects += 1;
+
+		let (apm, eapm) = self.apm_between(now, self.props.rolling_window);
+		let (session_apm, session_eapm) = self.session_apm(now);
+
+		let apm_text = format!("{:.0} APM", apm);
+		let apm_text = TextBlueprint {
+			text: &apm_text,
+			x: rect.x + padding,
+			y: rect.y + padding,
+			font,
+			size: 2. * font_size,
+			col: self.props.color,
+			alpha: 1.,
+		};
+		drawer.draw_text(&apm_text);
+
+		let details = format!("{:.0} EAPM · avg {:.0} / {:.0}", eapm, session_apm, session_eapm);
+		let details = TextBlueprint {
+			text: &details,
+			y: apm_text.y + apm_text.text_height() + padding,
+			size: font_size,
+			col: DETAIL_COLOR,
+			..apm_text
+		};
+		drawer.draw_text(&details);
+		drawn_texts += 2;
+
+		// EAPM graph, oldest on the left
+		let graph_top = details.y + details.text_height() + padding;
+		let graph_height = rect.y + rect.h - padding - graph_top;
+		let n_bars = self.props.graph_bars.max(1);
+
+		if graph_height <= 0. || self.props.graph_duration <= 0. {
+			return (drawn_rects, drawn_texts);
+		}
+
+		let bar_duration = self.props.graph_duration / n_bars as f32;
+		let bar_width = (rect.w - 2. * padding) / n_bars as f32;
+
+		let bars = (0..n_bars)
+			.map(|i| {
+				let end = now - Duration::from_secs_f32(bar_duration * (n_bars - 1 - i) as f32);
+				self.apm_between(end, bar_duration).1
+			})
+			.collect::<Vec<_>>();
+
+		let max_eapm = bars.iter().copied().fold(self.props.graph_max, f32::max);
+
+		for (i, eapm) in bars.into_iter().enumerate() {
+			let bar_height = graph_height * eapm / max_eapm.max(1.);
+			if bar_height <= 0. {
+				continue;
+			}
+
+			drawer.draw_rect(&RectBlueprint {
+				rect: Rect::new(
+					rect.x + padding + i as f32 * bar_width,
+					graph_top + graph_height - bar_height,
+					bar_width * 0.8,
+					bar_height,
+				),
+				color: self.props.graph_color,
+				border_color: self.props.graph_color,
+				border_width: 0.,
+				corner_radius: 0.,
+				borders: [false, false, false, false],
+				alpha: 1.,
+			});
+			drawn_rects += 1;
+		}
+
+		(drawn_rects, drawn_texts)
+	}
+}
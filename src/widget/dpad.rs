@@ -0,0 +1,137 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+use glam::{vec2, Vec2};
+use loki_draw::drawer::{Drawer, RectBlueprint};
+use loki_draw::rect::Rect;
+
+use crate::config::DpadProps;
+use crate::direction::{Direction, DirectionInput};
+use crate::KeyEvent;
+
+const MAX_HISTORY: usize = 64;
+const CELL_GAP: f32 = 0.08;
+
+/// 3x3 grid lighting up the direction resolved from four keys.
+pub struct Dpad {
+	props: DpadProps,
+	input: DirectionInput,
+	/// Directions and the time they started, most recent first
+	history: VecDeque<(Direction, SystemTime)>,
+}
+
+impl Dpad {
+	pub fn new(props: DpadProps) -> Self {
+		Self {
+			input: DirectionInput::new(props.directions, props.socd),
+			history: VecDeque::with_capacity(MAX_HISTORY),
+			props,
+		}
+	}
+
+	pub fn keys(&self) -> Vec<rdev::Key> {
+		self.props.directions.as_array().to_vec()
+	}
+
+	pub fn key_event(&mut self, event: &KeyEvent) {
+		if !self.input.set_key_pressed(event.key, event.pressed) {
+			return;
+		}
+
+		let direction = self.input.direction();
+		if self.history.front().is_some_and(|&(last, _)| last == direction) {
+			return;
+		}
+
+		if self.history.len() >= MAX_HISTORY {
+			self.history.pop_back();
+		}
+
+		self.history.push_front((direction, event.time));
+	}
+
+	/// Opacity of each cell's trail, in numpad order starting from 1.
+	fn trail_alphas(&self, now: SystemTime) -> [f32; 9] {
+		let mut alphas = [0.; 9];
+
+		if self.props.trail_duration == 0 {
+			return alphas;
+		}
+
+		let trail_duration = Duration::from_millis(self.props.trail_duration);
+
+		// past directions fade out from the moment they were left
+		let past = self.history.iter().skip(1).zip(&self.history);
+		for (&(direction, _), &(_, end)) in past {
+			let age = now.duration_since(end).unwrap_or_default();
+			if age >= trail_duration {
+				break;
+			}
+
+			let cell = &mut alphas[cell_index(direction)];
+			let alpha = self.props.alpha * (1. - age.as_secs_f32() / trail_duration.as_secs_f32());
+			*cell = cell.max(alpha);
+		}
+
+		alphas
+	}
+
+	pub fn draw(&self, drawer: &mut impl Drawer, scale: Vec2, now: SystemTime) -> (usize, usize) {
+		let rect = self.props.rect.to_rect(scale);
+
+		// the grid is kept square, centered in the widget
+		let cell_size = rect.w.min(rect.h) / 3.;
+		let gap = cell_size * CELL_GAP;
+		let origin = vec2(rect.x + rect.w / 2., rect.y + rect.h / 2.) - 1.5 * cell_size;
+
+		let current = self.input.direction();
+		let mut drawn_rects = 0;
+
+		for (i, trail_alpha) in self.trail_alphas(now).into_iter().enumerate() {
+			let direction = Direction::from_numpad(i as u8 + 1).unwrap();
+			let cell_pos = origin + vec2((direction.x + 1) as f32, (1 - direction.y) as f32) * cell_size;
+			let cell_pos = cell_pos + gap / 2.;
+			let cell_size = cell_size - gap;
+
+			let (color, alpha) = match direction == current {
+				true => (self.props.color, 1.),
+				false => (self.props.background_color, self.props.alpha),
+			};
+
+			drawer.draw_rect(&RectBlueprint {
+				rect: Rect::new(cell_pos.x, cell_pos.y, cell_size, cell_size),
+				color,
+				border_color: self.props.border_color,
+				border_width: gap,
+				corner_radius: 2.,
+				borders: [true, true, true, true],
+				alpha,
+			});
+			drawn_rects += 1;
+
+			if direction != current && trail_alpha > 0. {
+				drawer.draw_rect(&RectBlueprint {
+					rect: Rect::new(
+						cell_pos.x + gap,
+						cell_pos.y + gap,
+						cell_size - 2. * gap,
+						cell_size - 2. * gap,
+					),
+					color: self.props.color,
+					border_color: self.props.color,
+					border_width: 0.,
+					corner_radius: 0.,
+					borders: [false, false, false, false],
+					alpha: trail_alpha,
+				});
+				drawn_rects += 1;
+			}
+		}
+
+		(drawn_rects, 0)
+	}
+}
+
+fn cell_index(direction: Direction) -> usize {
+	((direction.y + 1) * 3 + direction.x + 1) as usize
+}
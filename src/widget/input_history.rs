@@ -0,0 +1,275 @@
+use std::collections::{HashSet, VecDeque};
+use std::error::Error;
+use std::time::{Duration, SystemTime};
+
+use glam::Vec2;
+use loki_draw::drawer::{Drawer, RectBlueprint, TextBlueprint};
+use loki_draw::font::Font;
+use loki_draw::rect::Rect;
+
+use crate::config::InputHistoryProps;
+use crate::direction::{Direction, DirectionInput};
+use crate::key::display_key;
+use crate::layout::DETAIL_COLOR;
+use crate::widget::PADDING;
+use crate::KeyEvent;
+
+const MAX_ENTRIES: usize = 64;
+const MAX_FRAMES: u32 = 99;
+
+struct Motion {
+	name: String,
+	sequence: Vec<Direction>,
+	window: Duration,
+	color: u32,
+}
+
+/// One line of the history: a direction and the buttons pressed along with it.
+struct Entry {
+	direction: Direction,
+	/// Indices of the pressed buttons, in press order
+	buttons: Vec<usize>,
+	time: SystemTime,
+	/// Motion completed by this entry
+	motion: Option<usize>,
+}
+
+/// Scrolling list of recent inputs, fighting game style.
+pub struct InputHistory {
+	props: InputHistoryProps,
+	input: DirectionInput,
+	button_labels: Vec<String>,
+	motions: Vec<Motion>,
+	held_buttons: HashSet<rdev::Key>,
+	/// Most recent first
+	entries: VecDeque<Entry>,
+}
+
+impl InputHistory {
+	pub fn new(props: InputHistoryProps) -> Result<Self, Box<dyn Error>> {
+		let motions = (props.motions.iter())
+			.map(|motion| {
+				let sequence = (motion.sequence.iter())
+					.map(|&n| {
+						Direction::from_numpad(n).ok_or_else(|| {
+							format!(
+								"invalid direction {} in motion {}, expected numpad notation (1 to 9)",
+								n, motion.name
+							)
+						})
+					})
+					.collect::<Result<Vec<_>, _>>()?;
+
+				Ok::<_, String>(Motion {
+					name: motion.name.clone(),
+					sequence,
+					window: Duration::from_millis(motion.window),
+					color: motion.color,
+				})
+			})
+			.collect::<Result<Vec<_>, _>>()?;
+
+		let button_labels = (props.buttons.iter())
+			.map(|button| match &button.label {
+				Some(label) => label.clone(),
+				None => display_key(button.key).to_string(),
+			})
+			.collect();
+
+		Ok(Self {
+			input: DirectionInput::new(props.directions, props.socd),
+			button_labels,
+			motions,
+			held_buttons: HashSet::new(),
+			entries: VecDeque::with_capacity(MAX_ENTRIES),
+			props,
+		})
+	}
+
+	pub fn keys(&self) -> Vec<rdev::Key> {
+		let buttons = self.props.buttons.iter().map(|button| button.key);
+		self.props.directions.as_array().into_iter().chain(buttons).collect()
+	}
+
+	pub fn key_event(&mut self, event: &KeyEvent) {
+		let last_direction = self.input.direction();
+
+		if self.input.set_key_pressed(event.key, event.pressed) {
+			let direction = self.input.direction();
+			if direction != last_direction {
+				self.push(direction, None, event.time);
+			}
+		} else if let Some(button) = self.props.buttons.iter().position(|button| button.key == event.key) {
+			if !event.pressed {
+				self.held_buttons.remove(&event.key);
+				return;
+			}
+
+			// key repeats aren't new presses
+			if self.held_buttons.insert(event.key) {
+				self.push(last_direction, Some(button), event.time);
+			}
+		}
+	}
+
+	fn push(&mut self, direction: Direction, button: Option<usize>, time: SystemTime) {
+		let group_window = Duration::from_millis(self.props.group_window);
+
+		// near-simultaneous inputs end up on the same line
+		let grouped = (self.entries.front_mut())
+			.filter(|entry| time.duration_since(entry.time).is_ok_and(|dt| dt <= group_window));
+
+		match grouped {
+			Some(entry) => {
+				entry.direction = direction;
+				if let Some(button) = button.filter(|button| !entry.buttons.contains(button)) {
+					entry.buttons.push(button);
+				}
+			}
+			None => {
+				if self.entries.len() >= MAX_ENTRIES {
+					self.entries.pop_back();
+				}
+
+				self.entries.push_front(Entry {
+					direction,
+					buttons: button.into_iter().collect(),
+					time,
+					motion: None,
+				});
+			}
+		}
+
+		let motion = self.find_motion();
+		self.entries[0].motion = motion;
+	}
+
+	/// First motion ending with the newest entry.
+	///
+	/// Steps only have to appear in order within the motion's window,
+	/// so sloppy inputs like `2 1 2 3 6` still count as a quarter-circle.
+	fn find_motion(&self) -> Option<usize> {
+		let newest = self.entries.front()?;
+
+		self.motions.iter().position(|motion| {
+			let mut steps = motion.sequence.iter().rev().peekable();
+
+			if steps.peek() != Some(&&newest.direction) {
+				return false;
+			}
+
+			for entry in &self.entries {
+				if newest.time.duration_since(entry.time).unwrap_or_default() > motion.window {
+					break;
+				}
+
+				if steps.peek() == Some(&&entry.direction) {
+					steps.next();
+				}
+
+				if steps.peek().is_none() {
+					return true;
+				}
+			}
+
+			false
+		})
+	}
+
+	pub fn draw(&self, drawer: &mut impl Drawer, font: &Font, scale: Vec2, now: SystemTime) -> (usize, usize) {
+		let mut drawn_rects = 0;
+		let mut drawn_texts = 0;
+
+		let rect = self.props.rect.to_rect(scale);
+		let padding = PADDING * scale.y;
+		let font_size = self.props.font_size * scale.y;
+
+		drawer.draw_rect(&RectBlueprint {
+			rect: self.props.rect.to_rect(scale),
+			color: self.props.background_color,
+			border_color: self.props.background_color,
+			border_width: 0.,
+			corner_radius: 2.,
+			borders: [false, false, false, false],
+			alpha: self.props.alpha,
+		});
+		drawn_rects += 1;
+
+		let text = TextBlueprint {
+			text: "",
+			x: rect.x + padding,
+			y: rect.y + padding,
+			font,
+			size: font_size,
+			col: self.props.color,
+			alpha: 1.,
+		};
+
+		let frames_width = TextBlueprint {
+			text: &MAX_FRAMES.to_string(),
+			..text
+		}
+		.text_width();
+		let row_height = text.text_height() + padding;
+
+		let mut end_time = now;
+		for (i, entry) in self.entries.iter().enumerate() {
+			let y = rect.y + padding + i as f32 * row_height;
+			if y + row_height > rect.y + rect.h {
+				break;
+			}
+
+			// how long the entry lasted, the newest one lasting until now
+			let duration = end_time.duration_since(entry.time).unwrap_or_default();
+			let frames = ((duration.as_secs_f32() * self.props.frame_rate).round() as u32).min(MAX_FRAMES);
+			end_time = entry.time;
+
+			if let Some(motion) = entry.motion.map(|m| &self.motions[m]) {
+				drawer.draw_rect(&RectBlueprint {
+					rect: Rect::new(rect.x, y - padding / 2., rect.w, row_height),
+					color: motion.color,
+					border_color: motion.color,
+					border_width: 0.,
+					corner_radius: 0.,
+					borders: [false, false, false, false],
+					alpha: 0.25,
+				});
+				drawn_rects += 1;
+
+				let mut name_text = TextBlueprint {
+					text: &motion.name,
+					col: motion.color,
+					y,
+					..text
+				};
+				name_text.x = rect.x + rect.w - padding - name_text.text_width();
+				drawer.draw_text(&name_text);
+				drawn_texts += 1;
+			}
+
+			drawer.draw_text(&TextBlueprint {
+				text: &frames.to_string(),
+				x: rect.x + padding,
+				y,
+				col: DETAIL_COLOR,
+				..text
+			});
+
+			let mut inputs = entry.direction.glyph().to_string();
+			for &button in &entry.buttons {
+				inputs += " + ";
+				inputs += &self.button_labels[button];
+			}
+
+			drawer.draw_text(&TextBlueprint {
+				text: &inputs,
+				x: rect.x + 2. * padding + frames_width,
+				y,
+				..text
+			});
+			drawn_texts += 2;
+		}
+
+		(drawn_rects, drawn_texts)
+	}
+}
@@ -0,0 +1,141 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+use glam::{vec2, Vec2};
+use loki_draw::drawer::{Drawer, RectBlueprint, TextBlueprint};
+use loki_draw::font::Font;
+use loki_draw::rect::Rect;
+
+use crate::config::MouseProps;
+use crate::widget::PADDING;
+
+const MAX_SAMPLES: usize = 1024;
+const MAX_DOTS_PER_SEGMENT: usize = 16;
+const SPEED_WINDOW: Duration = Duration::from_millis(100);
+const SPEED_FONT_SIZE: f32 = 14.;
+const SPEED_COLOR: u32 = 0xeeeeee;
+
+/// Fading path of the cursor, centered on its current position.
+pub struct Mouse {
+	props: MouseProps,
+	/// Cursor positions in screen pixels, most recent first
+	samples: VecDeque<(Vec2, SystemTime)>,
+}
+
+impl Mouse {
+	pub fn new(props: MouseProps) -> Self {
+		Self {
+			props,
+			samples: VecDeque::with_capacity(MAX_SAMPLES),
+		}
+	}
+
+	pub fn mouse_move(&mut self, pos: Vec2, time: SystemTime) {
+		let trail_duration = Duration::from_millis(self.props.trail_duration);
+
+		while (self.samples.back()).is_some_and(|&(_, t)| {
+			self.samples.len() >= MAX_SAMPLES || time.duration_since(t).unwrap_or_default() > trail_duration
+		}) {
+			self.samples.pop_back();
+		}
+
+		self.samples.push_front((pos, time));
+	}
+
+	/// Cursor speed in screen pixels per second, over the last few moves.
+	fn speed(&self, now: SystemTime) -> f32 {
+		let recent =
+			(self.samples.iter()).take_while(|&&(_, t)| now.duration_since(t).unwrap_or_default() <= SPEED_WINDOW);
+		let distance = (recent.clone().zip(recent.skip(1)))
+			.map(|((a, _), (b, _))| a.distance(*b))
+			.sum::<f32>();
+
+		distance / SPEED_WINDOW.as_secs_f32()
+	}
+
+	pub fn draw(&self, drawer: &mut impl Drawer, font: &Font, scale: Vec2, now: SystemTime) -> (usize, usize) {
+		let mut drawn_rects = 0;
+		let mut drawn_texts = 0;
+
+		let rect = self.props.rect.to_rect(scale);
+		let center = vec2(rect.x + rect.w / 2., rect.y + rect.h / 2.);
+
+		drawer.draw_rect(&RectBlueprint {
+			rect: self.props.rect.to_rect(scale),
+			color: self.props.background_color,
+			border_color: self.props.background_color,
+			border_width: 0.,
+			corner_radius: 2.,
+			borders: [false, false, false, false],
+			alpha: self.props.alpha,
+		});
+		drawn_rects += 1;
+
+		let Some(&(current, _)) = self.samples.front() else {
+			return (drawn_rects, drawn_texts);
+		};
+
+		let dot_size = self.props.dot_size * scale.min_element();
+		let to_widget = |pos: Vec2| center + (pos - current) * self.props.scale * scale;
+		let trail_duration = self.props.trail_duration as f32 / 1000.;
+
+		let mut draw_dot = |pos: Vec2, size: f32, alpha: f32| {
+			let inside = pos.x - size / 2. >= rect.x
+				&& pos.y - size / 2. >= rect.y
+				&& pos.x + size / 2. <= rect.x + rect.w
+				&& pos.y + size / 2. <= rect.y + rect.h;
+
+			if inside {
+				drawer.draw_rect(&RectBlueprint {
+					rect: Rect::new(pos.x - size / 2., pos.y - size / 2., size, size),
+					color: self.props.color,
+					border_color: self.props.color,
+					border_width: 0.,
+					corner_radius: size / 2.,
+					borders: [false, false, false, false],
+					alpha,
+				});
+				drawn_rects += 1;
+			}
+		};
+
+		// path, dots being spread along each move so it doesn't look like a dotted line
+		for (&(newer, _), &(older, time)) in self.samples.iter().zip(self.samples.iter().skip(1)) {
+			let age = now.duration_since(time).unwrap_or_default().as_secs_f32();
+			if age >= trail_duration {
+				break;
+			}
+
+			let (from, to) = (to_widget(older), to_widget(newer));
+			let n_dots = ((from.distance(to) / dot_size).ceil() as usize).clamp(1, MAX_DOTS_PER_SEGMENT);
+			let alpha = 1. - age / trail_duration;
+
+			for i in 0..n_dots {
+				draw_dot(from.lerp(to, i as f32 / n_dots as f32), dot_size, alpha);
+			}
+		}
+
+		draw_dot(center, 2. * dot_size, 1.);
+
+		if self.props.display_speed {
+			let speed_text = format!("{:.0} px/s", self.speed(now));
+			let padding = PADDING * scale.y;
+
+			let mut text = TextBlueprint {
+				text: &speed_text,
+				x: rect.x + padding,
+				y: 0.,
+				font,
+				size: SPEED_FONT_SIZE * scale.y,
+				col: SPEED_COLOR,
+				alpha: 1.,
+			};
+			text.y = rect.y + rect.h - padding - text.text_height();
+
+			drawer.draw_text(&text);
+			drawn_texts += 1;
+		}
+
+		(drawn_rects, drawn_texts)
+	}
+}